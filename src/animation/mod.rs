@@ -0,0 +1,406 @@
+//! Keyframe animations, sampled by time
+
+use std::f32::consts::FRAC_PI_4;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::assets::transform::euler_rotation;
+use crate::math::Quat;
+
+/// Largest change of an Euler angle between two rotation keyframes, in radians, over which keyframes are added
+/// in between.
+///
+/// With each angle turning by at most an eighth of a turn, the rotation between two keyframes stays under half a
+/// turn, which is the way quaternions interpolate.
+const MAX_EULER_STEP: f32 = FRAC_PI_4;
+
+/// How values are computed between two keyframes
+#[derive(Deserialize, Copy, Clone, Debug, Default, PartialEq)]
+pub enum Interpolation {
+    /// Keeps the value of the previous keyframe until the next one is reached
+    Step,
+    /// Moves at constant speed from one keyframe to the next
+    #[default]
+    Linear,
+    /// Catmull-Rom spline through the keyframes, smooth at each keyframe
+    Cubic,
+}
+
+/// What happens once the time goes past the last keyframe
+#[derive(Deserialize, Copy, Clone, Debug, Default, PartialEq)]
+pub enum PlayMode {
+    /// Starts over from the first keyframe
+    #[default]
+    Loop,
+    /// Plays backwards to the first keyframe, then forwards again
+    PingPong,
+    /// Holds the value of the last keyframe
+    Clamp,
+}
+
+/// Values that can be animated
+pub trait Keyable: Copy {
+    /// Value at `t` between `a` and `b`, `t` going from 0 to 1
+    fn lerp(a: Self, b: Self, t: f32) -> Self;
+    /// Value at `t` between `p1` and `p2` on the Catmull-Rom spline through the four points
+    fn catmull_rom(p0: Self, p1: Self, p2: Self, p3: Self, t: f32) -> Self;
+}
+
+impl Keyable for f32 {
+    fn lerp(a: f32, b: f32, t: f32) -> f32 {
+        a + (b - a) * t
+    }
+
+    fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+        let t2 = t * t;
+        let t3 = t2 * t;
+        0.5 * (2.0 * p1
+            + (p2 - p0) * t
+            + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+            + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+    }
+}
+
+impl Keyable for [f32; 3] {
+    fn lerp(a: Self, b: Self, t: f32) -> Self {
+        [0, 1, 2].map(|i| f32::lerp(a[i], b[i], t))
+    }
+
+    fn catmull_rom(p0: Self, p1: Self, p2: Self, p3: Self, t: f32) -> Self {
+        [0, 1, 2].map(|i| f32::catmull_rom(p0[i], p1[i], p2[i], p3[i], t))
+    }
+}
+
+/// Rotations turn along the shortest arc between two keyframes
+impl Keyable for Quat {
+    fn lerp(a: Quat, b: Quat, t: f32) -> Quat {
+        a.slerp(b, t)
+    }
+
+    fn catmull_rom(p0: Quat, p1: Quat, p2: Quat, p3: Quat, t: f32) -> Quat {
+        // Each quaternion is flipped to the side of the previous one, for the spline not to go the long way around
+        let align = |q: Quat, previous: Quat| if q.dot(previous) < 0.0 { -q } else { q };
+        let p0 = align(p0, p1);
+        let p2 = align(p2, p1);
+        let p3 = align(p3, p2);
+        let [p0, p1, p2, p3] = [p0, p1, p2, p3].map(Quat::to_vec4);
+        let mut q = p1;
+        for i in 0..4 {
+            q[i] = f32::catmull_rom(p0[i], p1[i], p2[i], p3[i], t);
+        }
+        Quat::from(q).normalize()
+    }
+}
+
+/// Value taken at a given time
+#[derive(Deserialize, Copy, Clone, Debug)]
+pub struct Keyframe<T> {
+    /// Time, in seconds, from the start of the animation
+    pub time: f32,
+    /// Value of the animated field at `time`
+    pub value: T,
+}
+
+/// Keyframes of a single animated value
+#[derive(Deserialize, Clone, Debug)]
+pub struct Track<T> {
+    /// How values are computed between the keyframes
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    /// Keyframes sorted by time
+    pub keys: Vec<Keyframe<T>>,
+}
+
+impl<T> Default for Track<T> {
+    fn default() -> Self {
+        Track {
+            interpolation: Interpolation::default(),
+            keys: Vec::new(),
+        }
+    }
+}
+
+impl<T: Keyable> Track<T> {
+    /// Time of the last keyframe
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map_or(0.0, |key| key.time)
+    }
+
+    /// Value of the track at the given time, or `None` if it has no keyframe.
+    ///
+    /// Before the first keyframe and after the last one, their value is held.
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let keys = &self.keys;
+        // Index of the first keyframe after `time`
+        let next = keys.partition_point(|key| key.time <= time);
+        if next == 0 {
+            return keys.first().map(|key| key.value);
+        }
+        if next == keys.len() {
+            return keys.last().map(|key| key.value);
+        }
+
+        let (from, to) = (&keys[next - 1], &keys[next]);
+        let t = (time - from.time) / (to.time - from.time);
+        Some(match self.interpolation {
+            Interpolation::Step => from.value,
+            Interpolation::Linear => T::lerp(from.value, to.value, t),
+            Interpolation::Cubic => {
+                // The tangents at the ends are computed as if the end keyframes were repeated
+                let before = keys[next.saturating_sub(2)].value;
+                let after = keys.get(next + 1).unwrap_or(to).value;
+                T::catmull_rom(before, from.value, to.value, after, t)
+            }
+        })
+    }
+
+    /// Sorts the keyframes by time, as `sample` expects them
+    pub fn sort(&mut self) {
+        self.keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+}
+
+impl Track<[f32; 3]> {
+    /// Rotations of the keyframes, given as Euler angles like in scene files, see `euler_rotation`.
+    ///
+    /// Angles can turn by more than half a turn between two keyframes, full spins for instance, while quaternions
+    /// always take the shortest way. Keyframes sampled from the angles are added wherever any of them changes by
+    /// more than an eighth of a turn, so that the rotations follow the angles. The keyframes should be sorted.
+    pub fn to_rotations(&self) -> Track<Quat> {
+        let mut keys = Vec::with_capacity(self.keys.len());
+        for (i, key) in self.keys.iter().enumerate() {
+            if let Some(previous) = i.checked_sub(1).map(|i| &self.keys[i]) {
+                let change = (0..3).map(|axis| (key.value[axis] - previous.value[axis]).abs()).fold(0.0, f32::max);
+                let steps = (change / MAX_EULER_STEP).ceil() as usize;
+                if self.interpolation != Interpolation::Step && key.time > previous.time {
+                    for step in 1..steps {
+                        let time = previous.time + (key.time - previous.time) * step as f32 / steps as f32;
+                        let angles = self.sample(time).unwrap_or(key.value);
+                        keys.push(Keyframe { time, value: euler_rotation(angles) });
+                    }
+                }
+            }
+            keys.push(Keyframe { time: key.time, value: euler_rotation(key.value) });
+        }
+        Track { interpolation: self.interpolation, keys }
+    }
+}
+
+/// Reads a track of Euler angles, as written in scene files, into a track of rotations
+fn euler_track<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Track<Quat>, D::Error> {
+    let mut track = Track::<[f32; 3]>::deserialize(deserializer)?;
+    track.sort();
+    Ok(track.to_rotations())
+}
+
+/// Keyframe tracks for the transform of an object.
+///
+/// Each track overrides the matching field of the transform, fields without keyframes are left as they are.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct Animation {
+    /// What happens once the time goes past the last keyframe of the longest track
+    pub mode: PlayMode,
+    /// Keyframes of `Transform::translation`
+    pub translation: Track<[f32; 3]>,
+    /// Keyframes of `Transform::rotation`, read from Euler angles
+    #[serde(deserialize_with = "euler_track")]
+    pub rotation: Track<Quat>,
+    /// Keyframes of `Transform::rotate_self`, read from Euler angles
+    #[serde(deserialize_with = "euler_track")]
+    pub rotate_self: Track<Quat>,
+    /// Keyframes of `Transform::scale`
+    pub scale: Track<f32>,
+}
+
+impl Animation {
+    /// Time of the last keyframe of all tracks
+    pub fn duration(&self) -> f32 {
+        self.translation.duration()
+            .max(self.rotation.duration())
+            .max(self.rotate_self.duration())
+            .max(self.scale.duration())
+    }
+
+    /// Converts the time since the animation started into the time at which the tracks are sampled
+    pub fn local_time(&self, time: f32) -> f32 {
+        let duration = self.duration();
+        if duration <= 0.0 {
+            return 0.0;
+        }
+        match self.mode {
+            PlayMode::Loop => time.rem_euclid(duration),
+            PlayMode::PingPong => {
+                let time = time.rem_euclid(2.0 * duration);
+                if time > duration { 2.0 * duration - time } else { time }
+            }
+            PlayMode::Clamp => time.clamp(0.0, duration),
+        }
+    }
+
+    /// Sorts the keyframes of every track by time
+    pub fn sort(&mut self) {
+        self.translation.sort();
+        self.rotation.sort();
+        self.rotate_self.sort();
+        self.scale.sort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{PI, TAU};
+
+    use super::*;
+    use crate::math::Vec3;
+
+    fn track<T: Copy>(interpolation: Interpolation, keys: &[(f32, T)]) -> Track<T> {
+        Track { interpolation, keys: keys.iter().map(|&(time, value)| Keyframe { time, value }).collect() }
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{a} != {b}");
+    }
+
+    /// Animation lasting `duration` seconds, its translation going from the origin to `duration` along x
+    fn animation(mode: PlayMode, duration: f32) -> Animation {
+        Animation {
+            mode,
+            translation: track(Interpolation::Linear, &[(0.0, [0.0; 3]), (duration, [duration, 0.0, 0.0])]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn step_holds_the_previous_key() {
+        let steps = track(Interpolation::Step, &[(0.0, 1.0), (1.0, 5.0), (2.0, -3.0)]);
+        assert_eq!(steps.sample(0.0), Some(1.0));
+        assert_eq!(steps.sample(0.99), Some(1.0));
+        assert_eq!(steps.sample(1.0), Some(5.0));
+        assert_eq!(steps.sample(1.5), Some(5.0));
+    }
+
+    #[test]
+    fn linear_moves_at_constant_speed() {
+        let line = track(Interpolation::Linear, &[(1.0, 2.0), (3.0, 6.0)]);
+        assert_close(line.sample(2.0).unwrap(), 4.0);
+        assert_close(line.sample(1.5).unwrap(), 3.0);
+
+        let points = track(Interpolation::Linear, &[(0.0, [0.0, 2.0, -2.0]), (2.0, [4.0, 2.0, 2.0])]);
+        assert_eq!(points.sample(1.0), Some([2.0, 2.0, 0.0]));
+    }
+
+    #[test]
+    fn cubic_passes_through_the_keys() {
+        let keys = [(0.0, 0.0), (1.0, 3.0), (2.0, 1.0), (4.0, 2.0)];
+        let curve = track(Interpolation::Cubic, &keys);
+        for (time, value) in keys {
+            assert_close(curve.sample(time).unwrap(), value);
+        }
+
+        // Between the middle keys the tangents come from the keys on either side
+        assert_close(curve.sample(1.5).unwrap(), f32::catmull_rom(0.0, 3.0, 1.0, 2.0, 0.5));
+        // The end keys are repeated to compute the tangents of the first and last segments
+        assert_close(curve.sample(0.25).unwrap(), f32::catmull_rom(0.0, 0.0, 3.0, 1.0, 0.25));
+        assert_close(curve.sample(3.0).unwrap(), f32::catmull_rom(3.0, 1.0, 2.0, 2.0, 0.5));
+    }
+
+    #[test]
+    fn ends_are_held() {
+        let line = track(Interpolation::Linear, &[(1.0, 2.0), (3.0, 6.0)]);
+        assert_eq!(line.sample(-5.0), Some(2.0));
+        assert_eq!(line.sample(0.5), Some(2.0));
+        assert_eq!(line.sample(3.0), Some(6.0));
+        assert_eq!(line.sample(100.0), Some(6.0));
+
+        assert_eq!(track::<f32>(Interpolation::Cubic, &[]).sample(1.0), None);
+        assert_eq!(track(Interpolation::Cubic, &[(2.0, 7.0)]).sample(0.0), Some(7.0));
+    }
+
+    #[test]
+    fn play_modes() {
+        let looping = animation(PlayMode::Loop, 4.0);
+        assert_close(looping.local_time(1.0), 1.0);
+        assert_close(looping.local_time(5.0), 1.0);
+        assert_close(looping.local_time(-1.0), 3.0);
+
+        let ping_pong = animation(PlayMode::PingPong, 4.0);
+        assert_close(ping_pong.local_time(3.0), 3.0);
+        // Turns around at the end, then starts over once back at the start
+        assert_close(ping_pong.local_time(4.0), 4.0);
+        assert_close(ping_pong.local_time(5.0), 3.0);
+        assert_close(ping_pong.local_time(8.0), 0.0);
+        assert_close(ping_pong.local_time(9.0), 1.0);
+
+        let clamped = animation(PlayMode::Clamp, 4.0);
+        assert_close(clamped.local_time(2.0), 2.0);
+        assert_close(clamped.local_time(10.0), 4.0);
+        assert_close(clamped.local_time(-1.0), 0.0);
+    }
+
+    #[test]
+    fn zero_duration() {
+        let still = animation(PlayMode::Loop, 0.0);
+        assert_eq!(still.duration(), 0.0);
+        assert_eq!(still.local_time(3.0), 0.0);
+        assert_eq!(Animation::default().local_time(3.0), 0.0);
+    }
+
+    #[test]
+    fn duration_is_the_longest_track() {
+        let mut animation = animation(PlayMode::Loop, 2.0);
+        animation.scale = track(Interpolation::Step, &[(0.0, 1.0), (5.0, 2.0)]);
+        assert_eq!(animation.duration(), 5.0);
+    }
+
+    #[test]
+    fn sorting_keys() {
+        let mut animation = Animation {
+            scale: track(Interpolation::Linear, &[(2.0, 4.0), (0.0, 0.0), (1.0, 1.0)]),
+            ..Default::default()
+        };
+        animation.sort();
+        let times: Vec<f32> = animation.scale.keys.iter().map(|key| key.time).collect();
+        assert_eq!(times, [0.0, 1.0, 2.0]);
+        assert_eq!(animation.duration(), 2.0);
+        assert_close(animation.scale.sample(1.5).unwrap(), 2.5);
+    }
+
+    fn assert_same_rotation(a: Quat, b: Quat) {
+        assert!(a.dot(b).abs() > 1.0 - 1e-5, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn full_turns_keep_spinning() {
+        let angles = track(Interpolation::Linear, &[(0.0, [0.0; 3]), (8.0, [0.0, TAU, 0.0])]);
+        let rotations = angles.to_rotations();
+        assert_eq!(rotations.keys.len(), 9);
+        for time in [1.0, 3.5, 4.0, 6.2] {
+            assert_same_rotation(rotations.sample(time).unwrap(), euler_rotation(angles.sample(time).unwrap()));
+        }
+
+        let halfway = rotations.sample(4.0).unwrap();
+        assert!((halfway * Vec3::X - (-Vec3::X)).length() < 1e-5);
+    }
+
+    #[test]
+    fn small_changes_are_left_alone() {
+        let angles = track(Interpolation::Cubic, &[(0.0, [0.0; 3]), (1.0, [0.5, 0.0, 0.2]), (2.0, [0.0; 3])]);
+        assert_eq!(angles.to_rotations().keys.len(), 3);
+
+        let steps = track(Interpolation::Step, &[(0.0, [0.0; 3]), (1.0, [PI, 0.0, 0.0])]);
+        let rotations = steps.to_rotations();
+        assert_eq!(rotations.keys.len(), 2);
+        assert_same_rotation(rotations.sample(0.9).unwrap(), Quat::IDENTITY);
+    }
+
+    #[test]
+    fn cubic_rotations_pass_through_the_keyframes() {
+        let angles = track(Interpolation::Cubic, &[(0.0, [0.0; 3]), (1.0, [0.0, 0.7, 0.0]), (2.0, [0.3, 0.7, 0.0])]);
+        let rotations = angles.to_rotations();
+        for key in &angles.keys {
+            assert_same_rotation(rotations.sample(key.time).unwrap(), euler_rotation(key.value));
+        }
+        assert!((rotations.sample(0.5).unwrap().length() - 1.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,196 @@
+//! Application loop, separating input handling, simulation updates and rendering
+
+use glium::Display;
+use glium::glutin::event::Event;
+use glium::glutin::event_loop::EventLoop;
+
+use crate::assets::transform::{euler_rotation, Transform};
+use crate::camera::CameraMode;
+use crate::event_handler::actions::Controls;
+use crate::event_handler::EventHandler;
+use crate::model::renderer::Renderer;
+use crate::scene::{Scene, SceneObject};
+
+/// Title of the window, followed by the camera mode
+pub const WINDOW_TITLE: &str = "First";
+
+/// Angular speed of the directional lights around the x axis, in radians per second
+pub const LIGHT_ROTATION_SPEED: f32 = 1.2;
+
+/// How frames are paced
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FrameMode {
+    /// Waits for the vertical sync of the display before presenting each frame
+    Vsync,
+    /// Presents each frame as soon as it is rendered
+    Uncapped,
+}
+
+/// Everything needed to run the scene in a window
+pub struct App {
+    display: Display,
+    renderer: Renderer,
+    scene: Scene,
+    event_handler: EventHandler,
+    /// Whether the window currently grabs the cursor, lags behind `EventHandler::cursor_grabbed` until the next update
+    cursor_grabbed: bool,
+    /// Camera mode shown in the window title, lags behind `EventHandler::camera_mode` until the next update
+    camera_mode: CameraMode,
+    /// `None` when gamepads can't be read on this platform
+    #[cfg(feature = "gamepad")]
+    gamepads: Option<gilrs::Gilrs>,
+}
+
+impl App {
+    /// Creates the application, lighting the renderer with the lights of the scene
+    pub fn new(display: Display, mut renderer: Renderer, scene: Scene, controls: Controls) -> App {
+        renderer.ambient = scene.ambient;
+        renderer.lights = scene.world_lights(SceneObject::transform);
+
+        let event_handler = EventHandler {
+            grow: 0.1,
+            controls,
+            ..Default::default()
+        };
+
+        #[cfg(feature = "gamepad")]
+        let gamepads = gilrs::Gilrs::new()
+            .map_err(|e| eprintln!("Could not read gamepads: {e}"))
+            .ok();
+
+        let app = App {
+            display,
+            renderer,
+            scene,
+            camera_mode: event_handler.camera_mode,
+            event_handler,
+            cursor_grabbed: false,
+            #[cfg(feature = "gamepad")]
+            gamepads,
+        };
+        app.show_camera_mode();
+        app
+    }
+
+    /// Runs the application until the window is closed.
+    ///
+    /// Input events are handled as they arrive, then once they have all been handled the simulation
+    /// is updated and a single frame is requested.
+    pub fn run(mut self, event_loop: EventLoop<()>) -> ! {
+        event_loop.run(move |event, _, control_flow| match event {
+            Event::MainEventsCleared => {
+                self.update();
+                self.display.gl_window().window().request_redraw();
+            }
+            Event::RedrawRequested(_) => self.render(),
+            event => self.event_handler.handle_event(event, control_flow),
+        })
+    }
+
+    /// Advances the simulation by the time elapsed since the previous update
+    fn update(&mut self) {
+        #[cfg(feature = "gamepad")]
+        self.poll_gamepads();
+
+        let clock = &mut self.event_handler.clock;
+        let delta = clock.tick();
+        while clock.fixed_update() {
+            self.scene.update(clock.time());
+        }
+
+        // Uses real time so that the camera can still move while the simulation is paused
+        let world = self.scene.collision_world(|object| local_transform(&self.event_handler, object));
+        self.event_handler.update(self.event_handler.clock.real_delta(), &world);
+
+        self.renderer.lights = self.scene.world_lights(|object| local_transform(&self.event_handler, object));
+
+        let EventHandler { camera, controller, .. } = &mut self.event_handler;
+        if let Some(focus) = self.scene.focus {
+            camera.focus = self.scene.world_position(focus);
+        }
+        controller.update(camera);
+
+        // Only affects the rendering, so it follows the frame rather than the fixed steps
+        self.renderer.light_rotation += LIGHT_ROTATION_SPEED * delta;
+
+        if self.event_handler.cursor_grabbed != self.cursor_grabbed {
+            self.grab_cursor(self.event_handler.cursor_grabbed);
+        }
+
+        if self.event_handler.camera_mode != self.camera_mode {
+            self.camera_mode = self.event_handler.camera_mode;
+            self.show_camera_mode();
+        }
+
+        self.renderer.shadows = self.event_handler.shadows;
+        if let Err(e) = self.renderer.prepare_shadows(&self.display) {
+            eprintln!("Could not create shadow map: {e}");
+            self.event_handler.shadows = self.renderer.shadows;
+        }
+    }
+
+    /// Handles the buttons pressed and released on the gamepads since the previous update
+    #[cfg(feature = "gamepad")]
+    fn poll_gamepads(&mut self) {
+        use glium::glutin::event::ElementState;
+        use gilrs::EventType;
+
+        use crate::event_handler::actions::{Binding, GamepadButton};
+
+        let Some(gamepads) = &mut self.gamepads else { return };
+        while let Some(event) = gamepads.next_event() {
+            let (button, state) = match event.event {
+                EventType::ButtonPressed(button, _) => (button, ElementState::Pressed),
+                EventType::ButtonReleased(button, _) => (button, ElementState::Released),
+                _ => continue,
+            };
+            if let Some(button) = GamepadButton::from_gilrs(button) {
+                self.event_handler.handle_binding(Binding::Gamepad(button), state);
+            }
+        }
+    }
+
+    /// Locks the cursor to the window and hides it, or gives it back.
+    ///
+    /// Platforms that can't grab the cursor leave it free, the camera then only turns with the arrow keys.
+    fn grab_cursor(&mut self, grab: bool) {
+        let gl_window = self.display.gl_window();
+        let window = gl_window.window();
+        match window.set_cursor_grab(grab) {
+            Ok(()) => {
+                window.set_cursor_visible(!grab);
+                self.cursor_grabbed = grab;
+            }
+            Err(e) => {
+                eprintln!("Could not grab the cursor: {e}");
+                self.event_handler.cursor_grabbed = self.cursor_grabbed;
+            }
+        }
+    }
+
+    /// Names the current camera mode in the window title
+    fn show_camera_mode(&self) {
+        let title = format!("{WINDOW_TITLE} - {} camera", self.event_handler.controller.name());
+        self.display.gl_window().window().set_title(&title);
+    }
+
+    /// Draws a frame and presents it
+    fn render(&self) {
+        let App { display, renderer, scene, event_handler, .. } = self;
+
+        let mut target = display.draw();
+        renderer.draw_scene(display, &mut target, scene, &event_handler.camera, |object| local_transform(event_handler, object));
+        target.finish().unwrap();
+    }
+}
+
+/// Transform of an object relative to its parent, moved by the keyboard when the object is controllable
+fn local_transform<'a>(event_handler: &EventHandler, object: &'a SceneObject) -> Transform<'a> {
+    let mut transform = object.transform();
+    if object.controllable {
+        transform.rotate_self = euler_rotation([event_handler.spin, event_handler.tilt, 0.0]) * transform.rotate_self;
+        transform.translation[0] += event_handler.translate_x;
+        transform.translation[1] += event_handler.translate_y;
+    }
+    transform
+}
@@ -0,0 +1,67 @@
+//! Axis-aligned bounding boxes
+
+use serde::Deserialize;
+
+/// Axis-aligned bounding box
+#[derive(Deserialize, Copy, Clone, Debug, Default, PartialEq)]
+pub struct Aabb {
+    /// Corner with the smallest coordinates
+    pub min: [f32; 3],
+    /// Corner with the largest coordinates
+    pub max: [f32; 3],
+}
+
+impl Aabb {
+    /// Smallest box holding all the given points, or `None` if there are no points
+    pub fn from_points<I: IntoIterator<Item = [f32; 3]>>(points: I) -> Option<Aabb> {
+        points.into_iter().fold(None, |bounds, point| {
+            Some(match bounds {
+                None => Aabb { min: point, max: point },
+                Some(Aabb { min, max }) => Aabb {
+                    min: [0, 1, 2].map(|i| min[i].min(point[i])),
+                    max: [0, 1, 2].map(|i| max[i].max(point[i])),
+                },
+            })
+        })
+    }
+
+    /// Point in the middle of the box
+    pub fn center(&self) -> [f32; 3] {
+        [0, 1, 2].map(|i| (self.min[i] + self.max[i]) / 2.)
+    }
+
+    /// Width, height and depth of the box
+    pub fn size(&self) -> [f32; 3] {
+        [0, 1, 2].map(|i| self.max[i] - self.min[i])
+    }
+
+    /// Smallest box holding both boxes
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: [0, 1, 2].map(|i| self.min[i].min(other.min[i])),
+            max: [0, 1, 2].map(|i| self.max[i].max(other.max[i])),
+        }
+    }
+
+    /// Box grown by `margin` on every side
+    pub fn expanded(&self, margin: f32) -> Aabb {
+        Aabb {
+            min: self.min.map(|v| v - margin),
+            max: self.max.map(|v| v + margin),
+        }
+    }
+
+    /// Whether the point is strictly inside the box, points on its faces are outside
+    pub fn contains(&self, point: [f32; 3]) -> bool {
+        (0..3).all(|i| self.min[i] < point[i] && point[i] < self.max[i])
+    }
+
+    /// Smallest axis-aligned box holding this box once transformed by a column-major matrix
+    pub fn transformed(&self, matrix: &[[f32; 4]; 4]) -> Aabb {
+        let corners = (0..8).map(|corner| {
+            let point = [0, 1, 2].map(|i| if corner & (1 << i) == 0 { self.min[i] } else { self.max[i] });
+            [0, 1, 2].map(|row| (0..3).map(|column| matrix[column][row] * point[column]).sum::<f32>() + matrix[3][row])
+        });
+        Aabb::from_points(corners).unwrap_or_default()
+    }
+}
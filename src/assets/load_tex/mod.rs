@@ -1,38 +1,80 @@
-#[macro_export]
-macro_rules! load_tex {
-    // Macro that loads a texture from a png file.
-    ($display: expr, $path: expr, png) => {
-        {
-            let image = image::load(std::io::Cursor::new(&include_bytes!($path)),
-                image::ImageFormat::Png).unwrap().to_rgba8();
-            let image_dimensions = image.dimensions();
-            let image = glium::texture::RawImage2d::from_raw_rgba_reversed(&image.into_raw(), image_dimensions);
-            let texture = glium::texture::SrgbTexture2d::new($display, image).unwrap();
-            texture
-        }
-    };
-    // Macro that loads a texture from a jpeg file
-    ($display: expr, $path: expr, jpg) => {
-        {
-            let image = image::load(std::io::Cursor::new(&include_bytes!($path)),
-                image::ImageFormat::Jpeg).unwrap().to_rgba8();
-            let image_dimensions = image.dimensions();
-            let image = glium::texture::RawImage2d::from_raw_rgba_reversed(&image.into_raw(), image_dimensions);
-            let texture = glium::texture::SrgbTexture2d::new($display, image).unwrap();
-            texture
-        }
-    };
-
-
-    // Macro that loads a texture from a dds file
-    ($display: expr, $path: expr, dds) => {
-        {
-            let image = image::load(std::io::Cursor::new(&include_bytes!($path)),
-                image::ImageFormat::Dds).unwrap().to_rgba8();
-            let image_dimensions = image.dimensions();
-            let image = glium::texture::RawImage2d::from_raw_rgba_reversed(&image.into_raw(), image_dimensions);
-            let texture = glium::texture::SrgbTexture2d::new($display, image).unwrap();
-            texture
+//! Texture loading
+
+use std::{fmt, fs, io};
+use std::path::Path;
+
+use glium::backend::Facade;
+use glium::texture::{RawImage2d, SrgbTexture2d, TextureCreationError};
+use image::RgbaImage;
+
+/// Errors that can happen while loading a texture
+#[derive(Debug)]
+pub enum TextureError {
+    /// The file could not be read
+    Io(io::Error),
+    /// The file is not an image, or its format is not supported
+    Image(image::ImageError),
+    /// The texture could not be created on the GPU
+    Creation(TextureCreationError),
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextureError::Io(e) => write!(f, "could not read texture: {e}"),
+            TextureError::Image(e) => write!(f, "could not decode texture: {e}"),
+            TextureError::Creation(e) => write!(f, "could not create texture: {e}"),
         }
-    };
-}
\ No newline at end of file
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+impl From<io::Error> for TextureError {
+    fn from(e: io::Error) -> Self {
+        TextureError::Io(e)
+    }
+}
+
+impl From<image::ImageError> for TextureError {
+    fn from(e: image::ImageError) -> Self {
+        TextureError::Image(e)
+    }
+}
+
+impl From<TextureCreationError> for TextureError {
+    fn from(e: TextureCreationError) -> Self {
+        TextureError::Creation(e)
+    }
+}
+
+/// Loads a texture from an image file.
+///
+/// The format is detected from the contents of the file, and the image is flipped vertically
+/// so that its first row ends up at the bottom, as OpenGL expects.
+pub fn load_texture<F: Facade, P: AsRef<Path>>(display: &F, path: P) -> Result<SrgbTexture2d, TextureError> {
+    let image = load_image(path)?;
+    let image_dimensions = image.dimensions();
+    let image = RawImage2d::from_raw_rgba_reversed(&image.into_raw(), image_dimensions);
+    Ok(SrgbTexture2d::new(display, image)?)
+}
+
+/// Reads an image file into RGBA pixels, first row at the top.
+///
+/// The format is detected from the contents of the file.
+pub fn load_image<P: AsRef<Path>>(path: P) -> Result<RgbaImage, TextureError> {
+    let bytes = fs::read(path)?;
+    Ok(image::load_from_memory(&bytes)?.to_rgba8())
+}
+
+/// Creates a magenta and black checkerboard, shown in place of textures that failed to load
+pub fn placeholder_texture<F: Facade>(display: &F) -> Result<SrgbTexture2d, TextureError> {
+    let image = RawImage2d::from_raw_rgba(
+        vec![
+            255, 0, 255, 255, 0, 0, 0, 255,
+            0, 0, 0, 255, 255, 0, 255, 255,
+        ],
+        (2, 2),
+    );
+    Ok(SrgbTexture2d::new(display, image)?)
+}
@@ -1,8 +1,17 @@
-use std::f32::consts::PI;
-
-use glium::{Frame, Surface};
-
-/// Macros to create the matrices of the various transforms used in the program
+//! Macros to create the matrices of the various transforms used in the program.
+//!
+//! The matrices are column-major, as glium uploads them, and the macros are exported at the root of the crate:
+//!
+//! ```
+//! use ogl_house_scene::{rotate, scale, translate};
+//! use ogl_house_scene::assets::matrices::multiply_matrices;
+//!
+//! let angle = 0.5f32;
+//! let model = multiply_matrices(&translate![1.0, 0.0, 2.0], &multiply_matrices(&rotate![angle, y], &scale![0.25]));
+//! assert_eq!(model[3], [1.0, 0.0, 2.0, 1.0]);
+//! ```
+
+use crate::math::Mat4;
 
 /// Creates a matrix that translates the object by the given amount
 #[macro_export]
@@ -41,7 +50,9 @@ macro_rules! translate {
     }
 }
 
-/// Creates a matrix that rotates the object by the given angle
+/// Creates a matrix that rotates the object by the given angle around a single axis.
+///
+/// Rotations around several axes are built with `math::Quat::from_euler`, which takes the order to apply them in.
 #[macro_export]
 macro_rules! rotate {
     [$param:expr, x] => {
@@ -68,14 +79,6 @@ macro_rules! rotate {
             [0.0, 0.0, 0.0, 1.0 as f32],
         ]
     };
-    ($x: expr, $y: expr, $z: expr) => {
-        [
-            [$z.cos() * $y.cos(), $z.cos() * $y.sin() * $x.sin() - $z.sin() * $x.cos(), $z.cos() * $y.sin() * $x.cos() + $z.sin() * $x.sin(), 0.0],
-            [$z.sin() * $y.cos(), $z.sin() * $y.sin() * $x.sin() + $z.cos() * $x.cos(), $z.sin() * $y.sin() * $x.cos() - $z.cos() * $x.sin(), 0.0],
-            [-$y.sin(), $y.cos() * $x.sin(), $x.cos() * $y.cos(), 0.0],
-            [0.0, 0.0, 0.0, 1.0f32],
-        ]
-    }
 }
 
 
@@ -108,49 +111,20 @@ macro_rules! identity {
 /// Function that generates the perspective matrix
 pub fn perspective_matrix(dimensions: (u32, u32), fov: f32, zfar: f32, znear: f32) -> [[f32; 4]; 4] {
     let (width, height) = dimensions;
-    let aspect_ratio = height as f32 / width as f32;
-
-    let f: f32 = 1.0 / (fov / 2.0).tan();
-
-    [
-        [f * aspect_ratio, 0.0, 0.0, 0.0],
-        [0.0, f, 0.0, 0.0],
-        [0.0, 0.0, (zfar + znear) / (zfar - znear), 1.0],
-        [0.0, 0.0, -(2.0 * zfar * znear) / (zfar - znear), 0.0],
-    ]
+    Mat4::perspective(width as f32 / height as f32, fov, znear, zfar).into()
 }
 
-/// Function that generates the view matrix
+/// Function that generates the view matrix, see `Mat4::look_to` for what happens with degenerate directions
 pub fn view_matrix(position: &[f32; 3], direction: &[f32; 3], up: &[f32; 3]) -> [[f32; 4]; 4] {
-    let f = {
-        let f = direction;
-        let len = f[0] * f[0] + f[1] * f[1] + f[2] * f[2];
-        let len = len.sqrt();
-        [f[0] / len, f[1] / len, f[2] / len]
-    };
-
-    let s = [up[1] * f[2] - up[2] * f[1],
-        up[2] * f[0] - up[0] * f[2],
-        up[0] * f[1] - up[1] * f[0]];
-
-    let s_norm = {
-        let len = s[0] * s[0] + s[1] * s[1] + s[2] * s[2];
-        let len = len.sqrt();
-        [s[0] / len, s[1] / len, s[2] / len]
-    };
-
-    let u = [f[1] * s_norm[2] - f[2] * s_norm[1],
-        f[2] * s_norm[0] - f[0] * s_norm[2],
-        f[0] * s_norm[1] - f[1] * s_norm[0]];
+    Mat4::look_to((*position).into(), (*direction).into(), (*up).into()).into()
+}
 
-    let p = [-position[0] * s_norm[0] - position[1] * s_norm[1] - position[2] * s_norm[2],
-        -position[0] * u[0] - position[1] * u[1] - position[2] * u[2],
-        -position[0] * f[0] - position[1] * f[1] - position[2] * f[2]];
+/// Function that generates an orthographic projection matrix, looking down the positive z axis like `view_matrix`
+pub fn orthographic_matrix(half_width: f32, half_height: f32, znear: f32, zfar: f32) -> [[f32; 4]; 4] {
+    Mat4::orthographic(half_width, half_height, znear, zfar).into()
+}
 
-    [
-        [s_norm[0], u[0], f[0], 0.0],
-        [s_norm[1], u[1], f[1], 0.0],
-        [s_norm[2], u[2], f[2], 0.0],
-        [p[0], p[1], p[2], 1.0],
-    ]
+/// Multiplies two matrices, the result applies `b` first and then `a`
+pub fn multiply_matrices(a: &[[f32; 4]; 4], b: &[[f32; 4]; 4]) -> [[f32; 4]; 4] {
+    (Mat4::from(*a) * Mat4::from(*b)).into()
 }
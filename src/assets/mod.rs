@@ -1,3 +1,6 @@
+//! Building blocks of the models: vertices, bounding boxes, transforms, matrices and textures
+
+pub mod bounds;
 pub mod matrices;
 pub mod transform;
 pub mod vertex;
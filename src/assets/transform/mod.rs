@@ -1,29 +1,42 @@
+//! Placement of an object in the world and the camera it is seen through
+
 use std::f32::consts::PI;
-use glium::{Frame, texture::SrgbTexture2d};
-use crate::{identity, rotate, scale, translate};
-use crate::assets::matrices::{perspective_matrix, view_matrix};
+use glium::texture::SrgbTexture2d;
+use crate::{identity, scale, translate};
+use crate::assets::matrices::{multiply_matrices, perspective_matrix, view_matrix};
+use crate::math::{EulerOrder, Mat3, Mat4, Quat, Vec3};
+
+/// Rotation matching the `[x, y, z]` angles of scene files, in radians.
+///
+/// The object turns clockwise, looking down each axis, by `z` around the z axis first, then by `y` around y and
+/// by `x` around x.
+pub fn euler_rotation(angles: [f32; 3]) -> Quat {
+    Quat::from_euler(EulerOrder::Zyx, -Vec3::from(angles))
+}
 
 /// Struct that holds the transform parameters of a drawable object.
 pub struct Transform<'a> {
     /// Translate in [x, y, z]
     pub translation: [f32; 3],
-    /// Rotate in [x, y, z]
-    pub rotation: [f32; 3],
-    /// Rotate object around itself in [x, y, z]
-    pub rotate_self: [f32; 3],
+    /// Rotation around the origin of the parent
+    pub rotation: Quat,
+    /// Rotation of the object around itself
+    pub rotate_self: Quat,
     /// Scale in s
     pub scale: f32,
+    /// World matrix of the frame the object is placed in, identity for objects at the root of the scene
+    pub parent: [[f32; 4]; 4],
     /// View in [position, direction, up]
     pub view: [[f32; 3]; 3],
     /// Frame
     pub frame_dimensions: Option<(u32, u32)>,
-    // Texture
+    /// Texture of the submeshes whose material has none
     pub texture: Option<&'a glium::texture::SrgbTexture2d>,
-    // Zfar
+    /// Distance to the far clipping plane
     pub zfar: f32,
-    // Znear
+    /// Distance to the near clipping plane
     pub znear: f32,
-    // fov
+    /// Vertical field of view, in radians
     pub fov: f32,
 
 }
@@ -32,9 +45,10 @@ impl Default for Transform<'_> {
     fn default() -> Self {
         Transform {
             translation: [0.0, 0.0, 0.0],
-            rotation: [0.0, 0.0, 0.0],
-            rotate_self: [0.0, 0.0, 0.0],
+            rotation: Quat::IDENTITY,
+            rotate_self: Quat::IDENTITY,
             scale: 0.25,
+            parent: identity!(),
             view: [[1.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
             frame_dimensions: None,
             texture: None,
@@ -46,34 +60,117 @@ impl Default for Transform<'_> {
 }
 
 impl Transform<'_> {
+    /// Matrix of `translation`
     pub fn get_translation(&self) -> [[f32; 4]; 4] {
         translate!(self.translation[0], self.translation[1], self.translation[2])
     }
 
+    /// Matrix of `rotation`, turning the object around the origin of its parent
     pub fn get_rotation(&self) -> [[f32; 4]; 4] {
-        rotate!(self.rotation[0], self.rotation[1], self.rotation[2])
+        self.rotation.to_mat4().into()
     }
 
+    /// Matrix of `scale`
     pub fn get_scaling(&self) -> [[f32; 4]; 4] {
         scale!(self.scale)
     }
 
+    /// Matrix of `rotate_self`, turning the object around its own origin
     pub fn get_self_rotation(&self) -> [[f32; 4]; 4] {
-        rotate!(self.rotate_self[0], self.rotate_self[1], self.rotate_self[2])
+        self.rotate_self.to_mat4().into()
+    }
+
+    /// Matrix of the frame the object is placed in
+    pub fn get_parent(&self) -> [[f32; 4]; 4] {
+        self.parent
+    }
+
+    /// World matrix of the object, sent to the vertex shaders as `model`
+    pub fn get_model(&self) -> [[f32; 4]; 4] {
+        let local = multiply_matrices(
+            &multiply_matrices(&self.get_rotation(), &self.get_translation()),
+            &multiply_matrices(&self.get_scaling(), &self.get_self_rotation()),
+        );
+        multiply_matrices(&self.parent, &local)
+    }
+
+    /// Matrix turning the normals of the mesh into world space, the inverse transpose of the model matrix.
+    ///
+    /// It keeps normals perpendicular to their surfaces where the model matrix alone wouldn't, and is the
+    /// identity when the model matrix can't be inverted, for a zero scale.
+    pub fn get_normal_matrix(&self) -> [[f32; 3]; 3] {
+        Mat3::from(Mat4::from(self.get_model())).inverse().map_or(Mat3::IDENTITY, |inverse| inverse.transpose()).into()
+    }
+
+    /// World matrix of the frame children of the object are placed in.
+    ///
+    /// It holds every transform of the object but its scale, which only applies to its own mesh.
+    pub fn get_frame(&self) -> [[f32; 4]; 4] {
+        let local = multiply_matrices(&self.get_rotation(), &multiply_matrices(&self.get_translation(), &self.get_self_rotation()));
+        multiply_matrices(&self.parent, &local)
     }
 
+    /// View matrix of `view`
     pub fn get_view(&self) -> [[f32; 4]; 4] {
         view_matrix(&self.view[0], &self.view[1], &self.view[2])
     }
 
-    pub fn get_perspective(&self) -> [[f32; 4]; 4] {
+    /// Perspective projection of the frame, identity until `frame_dimensions` is set
+    pub fn get_projection(&self) -> [[f32; 4]; 4] {
         match self.frame_dimensions {
             Some(dim) => perspective_matrix(dim, self.fov, self.zfar, self.znear),
             None => identity!(),
         }
     }
 
+    /// Texture of the object, panics when there is none
     pub fn get_texture(&self) -> &SrgbTexture2d {
         self.texture.unwrap()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rotation matrix the scene files used to be read with, turning by `z`, then `y`, then `x`, each clockwise
+    fn euler_matrix([x, y, z]: [f32; 3]) -> [[f32; 4]; 4] {
+        [
+            [z.cos() * y.cos(), z.cos() * y.sin() * x.sin() - z.sin() * x.cos(), z.cos() * y.sin() * x.cos() + z.sin() * x.sin(), 0.0],
+            [z.sin() * y.cos(), z.sin() * y.sin() * x.sin() + z.cos() * x.cos(), z.sin() * y.sin() * x.cos() - z.cos() * x.sin(), 0.0],
+            [-y.sin(), y.cos() * x.sin(), x.cos() * y.cos(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]
+    }
+
+    #[test]
+    fn normal_matrix() {
+        let transform = Transform {
+            rotation: euler_rotation([0.0, 0.5, 0.0]),
+            translation: [1.0, 2.0, 3.0],
+            rotate_self: euler_rotation([0.3, 0.0, 0.0]),
+            scale: 2.0,
+            ..Default::default()
+        };
+        let model = Mat4::from(transform.get_model());
+        let normals = Mat3::from(transform.get_normal_matrix());
+        // Normals follow the rotations of the surfaces, the scale of 2 becoming 1/2 in the inverse
+        let normal = Vec3::new(0.0, 0.6, 0.8);
+        let expected = model.transform_vector(normal) / 4.0;
+        assert!((normals * normal - expected).length() < 1e-5);
+
+        let flat = Transform { scale: 0.0, ..Default::default() };
+        assert_eq!(flat.get_normal_matrix(), <[[f32; 3]; 3]>::from(Mat3::IDENTITY));
+    }
+
+    #[test]
+    fn euler_rotation_matches_scene_files() {
+        for angles in [[0.0, 3.0, 0.0], [0.4, 0.0, 0.0], [0.0, 0.0, -1.2], [0.3, -2.0, 1.1], [1.5707964, 0.7, 0.2]] {
+            let transform = Transform { rotation: euler_rotation(angles), ..Default::default() };
+            let (actual, expected) = (transform.get_rotation(), euler_matrix(angles));
+            for (a, b) in actual.iter().flatten().zip(expected.iter().flatten()) {
+                assert!((a - b).abs() < 1e-5, "{angles:?}: {actual:?} != {expected:?}");
+            }
+        }
+    }
+}
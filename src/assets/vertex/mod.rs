@@ -1,16 +1,21 @@
+//! Vertex formats of the models
+
+/// Position and texture coordinates of a vertex, in the first vertex buffer of a model
 #[derive(Copy, Clone)]
 pub struct Vertex {
+    /// Position in model space
     pub position: [f32; 3],
+    /// Texture coordinates, with the origin at the bottom left of the texture
     pub tex_coords: [f32; 2],
 }
 
 implement_vertex!(Vertex, position, tex_coords);
 
+/// Normal of a vertex, in a second vertex buffer matching the first one
 #[derive(Copy, Clone)]
 pub struct Normal {
+    /// Unit normal in model space
     pub normal: [f32; 3],
 }
 
-implement_vertex!(Normal, normal);
-
-pub type Light = [f32; 3];
\ No newline at end of file
+implement_vertex!(Normal, normal);
\ No newline at end of file
@@ -0,0 +1,348 @@
+//! Camera of the scene and the controllers moving it around
+
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use crate::collision::CollisionWorld;
+use crate::math::{EulerOrder, Quat, Vec3};
+
+/// Keeps the camera from looking straight up or down, where its up vector would be parallel to its direction
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+/// Narrowest and widest field of view, in radians
+pub const FOV_RANGE: (f32, f32) = (0.1, 2.5);
+
+/// Change of the field of view, in radians, for each line scrolled
+const ZOOM_STEP: f32 = 0.05;
+
+/// Height of the eyes above the ground in FPS mode, in world units
+const EYE_HEIGHT: f32 = 0.15;
+
+/// Distance, in world units, the camera keeps from obstacles when it can collide with them
+const CAMERA_RADIUS: f32 = 0.05;
+
+/// Closest and farthest the orbit camera gets from its target, in world units
+const ORBIT_DISTANCE_RANGE: (f32, f32) = (0.2, 4.0);
+
+/// Relative change of the orbit distance for each line scrolled
+const ORBIT_ZOOM_STEP: f32 = 0.1;
+
+/// Unit vector the camera looks along for the given yaw and pitch, both zero looking down the z axis
+fn look_direction(yaw: f32, pitch: f32) -> Vec3 {
+    Vec3::new(pitch.cos() * yaw.sin(), pitch.sin(), pitch.cos() * yaw.cos())
+}
+
+/// Point of view the scene is rendered from
+#[derive(Copy, Clone, Debug)]
+pub struct Camera {
+    /// Position of the eye, in world units
+    pub position: [f32; 3],
+    /// Angle around the y axis, in radians, zero looking down the z axis
+    pub yaw: f32,
+    /// Angle above the horizon, in radians
+    pub pitch: f32,
+    /// Up direction of the world, which turning keeps the camera level with
+    pub up: [f32; 3],
+    /// Vertical field of view, in radians
+    pub fov: f32,
+    /// Distance to the near clipping plane, in world units
+    pub znear: f32,
+    /// Distance to the far clipping plane, in world units
+    pub zfar: f32,
+    /// Point of the world the orbit camera turns around
+    pub focus: [f32; 3],
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            position: [0.0, 0.1, 0.0],
+            yaw: 0.0,
+            pitch: 0.0,
+            up: [0.0, 1.0, 0.0],
+            fov: PI / 3.0,
+            znear: 0.1,
+            zfar: 30.0,
+            focus: [0.0; 3],
+        }
+    }
+}
+
+impl Camera {
+    /// Rotation turning the z axis to where the camera looks and the x axis to the right of the screen, pitching
+    /// the camera up before turning it around the y axis
+    pub fn orientation(&self) -> Quat {
+        Quat::from_euler(EulerOrder::Xyz, Vec3::new(-self.pitch, self.yaw, 0.0))
+    }
+
+    /// Unit vector the camera looks along
+    pub fn direction(&self) -> [f32; 3] {
+        (self.orientation() * Vec3::Z).into()
+    }
+
+    /// Unit vector pointing to the right of the screen, level with the horizon
+    pub fn right(&self) -> [f32; 3] {
+        (self.orientation() * Vec3::X).into()
+    }
+
+    /// View in [position, direction, up], as expected by `Transform::view`
+    pub fn view(&self) -> [[f32; 3]; 3] {
+        [self.position, self.direction(), self.up]
+    }
+
+    /// Turns the camera by the given angles, in radians, without letting it look straight up or down
+    pub fn turn(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = (self.yaw + yaw).rem_euclid(2.0 * PI);
+        self.pitch = (self.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Turns the camera towards a point, leaving it as it is when already there
+    pub fn look_at(&mut self, target: [f32; 3]) {
+        let Some(Vec3 { x, y, z }) = (Vec3::from(target) - Vec3::from(self.position)).try_normalize() else {
+            return;
+        };
+        self.yaw = x.atan2(z).rem_euclid(2.0 * PI);
+        self.pitch = y.asin().clamp(-MAX_PITCH, MAX_PITCH);
+    }
+}
+
+/// Translates input into camera motion, each controller giving the camera a different behaviour
+pub trait Controller {
+    /// Name shown in the window title while this controller is in use
+    fn name(&self) -> &'static str;
+
+    /// Called when the controller takes over the camera, to bring it to a position the controller allows
+    fn attach(&mut self, _camera: &mut Camera) {}
+
+    /// Called on every update, once the focus of the camera has moved along with the scene
+    fn update(&mut self, _camera: &mut Camera) {}
+
+    /// Moves the camera by the given distances along its forward, right and up axes, as far as `world` allows
+    fn move_by(&mut self, camera: &mut Camera, world: &CollisionWorld, forward: f32, right: f32, up: f32);
+
+    /// Turns the camera by the given angles, in radians
+    fn turn(&mut self, camera: &mut Camera, yaw: f32, pitch: f32) {
+        camera.turn(yaw, pitch);
+    }
+
+    /// Zooms in by the given number of scrolled lines, out when negative
+    fn zoom(&mut self, camera: &mut Camera, lines: f32) {
+        camera.fov = (camera.fov - lines * ZOOM_STEP).clamp(FOV_RANGE.0, FOV_RANGE.1);
+    }
+}
+
+/// The available camera controllers, in the order they are switched through
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum CameraMode {
+    /// Moves freely along where the camera looks, and straight up or down
+    #[default]
+    Fly,
+    /// Turns around the focused object, zooming towards or away from it
+    Orbit,
+    /// Walks on the ground at eye height
+    Fps,
+}
+
+impl CameraMode {
+    /// Mode switched to after this one
+    pub fn next(self) -> CameraMode {
+        match self {
+            CameraMode::Fly => CameraMode::Orbit,
+            CameraMode::Orbit => CameraMode::Fps,
+            CameraMode::Fps => CameraMode::Fly,
+        }
+    }
+
+    /// Creates the controller implementing this mode
+    pub fn controller(self) -> Box<dyn Controller> {
+        match self {
+            CameraMode::Fly => Box::new(FlyController),
+            CameraMode::Orbit => Box::new(OrbitController::default()),
+            CameraMode::Fps => Box::new(FpsController),
+        }
+    }
+}
+
+/// Moves along the direction the camera looks, and straight up or down
+pub struct FlyController;
+
+impl Controller for FlyController {
+    fn name(&self) -> &'static str {
+        "Fly"
+    }
+
+    fn move_by(&mut self, camera: &mut Camera, world: &CollisionWorld, forward: f32, right: f32, up: f32) {
+        let offset = Vec3::from(camera.direction()) * forward + Vec3::from(camera.right()) * right + Vec3::from(camera.up) * up;
+        camera.position = world.slide(camera.position, (Vec3::from(camera.position) + offset).into(), CAMERA_RADIUS);
+    }
+}
+
+/// Walks on the ground plane at eye height, looking around doesn't change where forward leads
+pub struct FpsController;
+
+impl Controller for FpsController {
+    fn name(&self) -> &'static str {
+        "FPS"
+    }
+
+    fn attach(&mut self, camera: &mut Camera) {
+        camera.position[1] = EYE_HEIGHT;
+    }
+
+    fn move_by(&mut self, camera: &mut Camera, world: &CollisionWorld, forward: f32, right: f32, _up: f32) {
+        let forward_axis = look_direction(camera.yaw, 0.0);
+        let right_axis = Vec3::from(camera.up).cross(forward_axis).normalize();
+        let offset = forward_axis * forward + right_axis * right;
+        camera.position = world.slide(camera.position, (Vec3::from(camera.position) + offset).into(), CAMERA_RADIUS);
+    }
+}
+
+/// Turns around the focus of the camera, always looking at it
+pub struct OrbitController {
+    /// Distance from the camera to its focus, in world units
+    distance: f32,
+}
+
+impl Default for OrbitController {
+    fn default() -> Self {
+        OrbitController { distance: 1.5 }
+    }
+}
+
+impl OrbitController {
+    /// Moves the camera back from its focus along the direction it looks
+    fn place(&self, camera: &mut Camera) {
+        camera.position = (Vec3::from(camera.focus) - look_direction(camera.yaw, camera.pitch) * self.distance).into();
+    }
+}
+
+impl Controller for OrbitController {
+    fn name(&self) -> &'static str {
+        "Orbit"
+    }
+
+    fn attach(&mut self, camera: &mut Camera) {
+        self.distance = Vec3::from(camera.position).distance(Vec3::from(camera.focus))
+            .clamp(ORBIT_DISTANCE_RANGE.0, ORBIT_DISTANCE_RANGE.1);
+        camera.look_at(camera.focus);
+        self.place(camera);
+    }
+
+    fn update(&mut self, camera: &mut Camera) {
+        self.place(camera);
+    }
+
+    /// Forward gets closer to the focus, sideways and vertical movement turn around it, going through obstacles
+    fn move_by(&mut self, camera: &mut Camera, _world: &CollisionWorld, forward: f32, right: f32, up: f32) {
+        self.distance = (self.distance - forward).clamp(ORBIT_DISTANCE_RANGE.0, ORBIT_DISTANCE_RANGE.1);
+        // Arc length to angle, so that the camera moves by about the same distance at any zoom
+        camera.turn(-right / self.distance, -up / self.distance);
+        self.place(camera);
+    }
+
+    fn turn(&mut self, camera: &mut Camera, yaw: f32, pitch: f32) {
+        camera.turn(yaw, pitch);
+        self.place(camera);
+    }
+
+    fn zoom(&mut self, camera: &mut Camera, lines: f32) {
+        self.distance = (self.distance * (1.0 - lines * ORBIT_ZOOM_STEP))
+            .clamp(ORBIT_DISTANCE_RANGE.0, ORBIT_DISTANCE_RANGE.1);
+        self.place(camera);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::bounds::Aabb;
+
+    /// Open space, nothing to collide with
+    fn world() -> CollisionWorld {
+        CollisionWorld { bounds: Aabb { min: [-100.0; 3], max: [100.0; 3] }, colliders: Vec::new() }
+    }
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        assert!((0..3).all(|i| (a[i] - b[i]).abs() < 1e-4), "{a:?} != {b:?}");
+    }
+
+    fn distance(camera: &Camera) -> f32 {
+        Vec3::from(camera.position).distance(Vec3::from(camera.focus))
+    }
+
+    /// Whether the camera looks straight at its focus
+    fn looks_at_focus(camera: &Camera) -> bool {
+        let to_focus = (Vec3::from(camera.focus) - Vec3::from(camera.position)).normalize();
+        to_focus.dot(Vec3::from(camera.direction())) > 0.9999
+    }
+
+    #[test]
+    fn orbit_attach_clamps_distance() {
+        let mut far = Camera { position: [1.0, 2.0, 3.0], focus: [1.0, 2.0, 13.0], ..Camera::default() };
+        OrbitController::default().attach(&mut far);
+        assert!((distance(&far) - ORBIT_DISTANCE_RANGE.1).abs() < 1e-4);
+        assert!(looks_at_focus(&far));
+
+        let mut close = Camera { position: [0.0, 0.0, 0.0], focus: [0.05, 0.05, 0.0], ..Camera::default() };
+        OrbitController::default().attach(&mut close);
+        assert!((distance(&close) - ORBIT_DISTANCE_RANGE.0).abs() < 1e-4);
+        assert!(looks_at_focus(&close));
+
+        // Within the range, the camera doesn't move
+        let mut within = Camera { position: [0.0, 1.0, -1.0], focus: [0.0, 0.0, 0.0], ..Camera::default() };
+        OrbitController::default().attach(&mut within);
+        assert_close(within.position, [0.0, 1.0, -1.0]);
+        assert!(looks_at_focus(&within));
+    }
+
+    #[test]
+    fn orbit_keeps_distance() {
+        let mut camera = Camera { position: [0.0, 0.0, -2.0], ..Camera::default() };
+        let mut orbit = OrbitController::default();
+        orbit.attach(&mut camera);
+
+        orbit.move_by(&mut camera, &world(), 0.0, 0.5, 0.3);
+        assert!((distance(&camera) - 2.0).abs() < 1e-4);
+        orbit.turn(&mut camera, 1.0, -0.4);
+        assert!((distance(&camera) - 2.0).abs() < 1e-4);
+        assert!(looks_at_focus(&camera));
+
+        // Forward gets closer, the focus moving drags the camera along
+        orbit.move_by(&mut camera, &world(), 0.5, 0.0, 0.0);
+        camera.focus = [3.0, 1.0, 0.0];
+        orbit.update(&mut camera);
+        assert!((distance(&camera) - 1.5).abs() < 1e-4);
+        assert!(looks_at_focus(&camera));
+    }
+
+    #[test]
+    fn fps_stays_at_eye_height() {
+        let mut camera = Camera { position: [0.0, 1.0, 0.0], yaw: FRAC_PI_2, pitch: 1.0, ..Camera::default() };
+        let mut fps = FpsController;
+        fps.attach(&mut camera);
+        assert_eq!(camera.position[1], EYE_HEIGHT);
+
+        // Looking up doesn't lift the camera, nor shorten its steps, and up is ignored
+        fps.move_by(&mut camera, &world(), 1.0, 0.0, 1.0);
+        assert_close(camera.position, [1.0, EYE_HEIGHT, 0.0]);
+        fps.move_by(&mut camera, &world(), 0.0, 0.5, 0.0);
+        assert_close(camera.position, [1.0, EYE_HEIGHT, -0.5]);
+    }
+
+    #[test]
+    fn pitch_is_clamped() {
+        let mut camera = Camera::default();
+        camera.turn(0.0, 10.0);
+        assert_eq!(camera.pitch, MAX_PITCH);
+        camera.turn(0.0, -20.0);
+        assert_eq!(camera.pitch, -MAX_PITCH);
+
+        camera.look_at([0.0, 5.0, 0.0]);
+        assert_eq!(camera.pitch, MAX_PITCH);
+        camera.look_at([0.0, -5.0, 0.0]);
+        assert_eq!(camera.pitch, -MAX_PITCH);
+
+        // Looking at its own position leaves the camera as it is
+        camera.look_at(camera.position);
+        assert_eq!(camera.pitch, -MAX_PITCH);
+    }
+}
@@ -0,0 +1,190 @@
+//! Time keeping, so that the simulation runs at the same speed whatever the frame rate
+
+use std::time::Instant;
+
+/// Duration, in seconds, of a fixed simulation step
+pub const FIXED_STEP: f32 = 1.0 / 60.0;
+
+/// Longest frame taken into account, so that a stall doesn't queue up a burst of fixed steps
+const MAX_FRAME_TIME: f32 = 0.25;
+
+/// Slowest and fastest the simulation can run, relative to real time
+const TIME_SCALE_RANGE: (f32, f32) = (1.0 / 16.0, 4.0);
+
+/// Measures the time between frames and splits it into fixed simulation steps
+pub struct Clock {
+    last_tick: Instant,
+    /// Real time elapsed between the last two ticks
+    real_delta: f32,
+    /// Simulated time not consumed by fixed steps yet
+    accumulator: f32,
+    /// Simulated time consumed by fixed steps since the clock started
+    time: f32,
+    /// Speed of the simulation relative to real time
+    pub time_scale: f32,
+    /// Whether the simulation is stopped, only advancing by steps
+    pub paused: bool,
+    /// Whether a single step was requested while paused
+    step_requested: bool,
+}
+
+impl Clock {
+    /// Advances the clock to now and returns the simulated time elapsed since the previous tick, in seconds.
+    ///
+    /// No time elapses while paused, except for one fixed step after `step` was called.
+    pub fn tick(&mut self) -> f32 {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_tick).as_secs_f32();
+        self.last_tick = now;
+        self.advance(elapsed)
+    }
+
+    /// Adds `elapsed` seconds of real time, as measured by `tick`, and returns the simulated time they make
+    fn advance(&mut self, elapsed: f32) -> f32 {
+        let elapsed = elapsed.min(MAX_FRAME_TIME);
+        self.real_delta = elapsed;
+
+        let delta = if !self.paused {
+            elapsed * self.time_scale
+        } else if std::mem::take(&mut self.step_requested) {
+            FIXED_STEP
+        } else {
+            0.0
+        };
+        self.accumulator += delta;
+        delta
+    }
+
+    /// Consumes one fixed step of the time accumulated by `tick`, returns false once there is not enough left
+    pub fn fixed_update(&mut self) -> bool {
+        if self.accumulator < FIXED_STEP {
+            return false;
+        }
+        self.accumulator -= FIXED_STEP;
+        self.time += FIXED_STEP;
+        true
+    }
+
+    /// Real time, in seconds, elapsed between the last two ticks, whether paused or slowed down.
+    ///
+    /// Used for what should keep responding while the simulation is paused, like the camera.
+    pub fn real_delta(&self) -> f32 {
+        self.real_delta
+    }
+
+    /// Simulated time, in seconds, consumed by fixed steps since the clock started
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Pauses the simulation, or resumes it
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Runs a single fixed step on the next tick, if paused
+    pub fn step(&mut self) {
+        if self.paused {
+            self.step_requested = true;
+        }
+    }
+
+    /// Multiplies the speed of the simulation by `factor`, within `TIME_SCALE_RANGE`
+    pub fn scale_time(&mut self, factor: f32) {
+        self.time_scale = (self.time_scale * factor).clamp(TIME_SCALE_RANGE.0, TIME_SCALE_RANGE.1);
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock {
+            last_tick: Instant::now(),
+            real_delta: 0.0,
+            accumulator: 0.0,
+            time: 0.0,
+            time_scale: 1.0,
+            paused: false,
+            step_requested: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-6, "{a} != {b}");
+    }
+
+    /// Number of fixed steps the accumulated time is enough for
+    fn run_steps(clock: &mut Clock) -> usize {
+        std::iter::from_fn(|| clock.fixed_update().then_some(())).count()
+    }
+
+    #[test]
+    fn fixed_steps_keep_the_remainder() {
+        let mut clock = Clock::default();
+        assert_close(clock.advance(2.5 * FIXED_STEP), 2.5 * FIXED_STEP);
+        assert_eq!(run_steps(&mut clock), 2);
+        assert_close(clock.time(), 2.0 * FIXED_STEP);
+        assert_close(clock.accumulator, 0.5 * FIXED_STEP);
+
+        // The remainder adds up with the next frame
+        clock.advance(0.6 * FIXED_STEP);
+        assert_eq!(run_steps(&mut clock), 1);
+        assert_close(clock.accumulator, 0.1 * FIXED_STEP);
+    }
+
+    #[test]
+    fn long_frames_are_capped() {
+        let mut clock = Clock::default();
+        assert_eq!(clock.advance(3.0), MAX_FRAME_TIME);
+        assert_eq!(clock.real_delta(), MAX_FRAME_TIME);
+        assert_eq!(run_steps(&mut clock), 15);
+    }
+
+    #[test]
+    fn time_scale() {
+        let mut clock = Clock::default();
+        clock.scale_time(0.5);
+        assert_close(clock.advance(0.1), 0.05);
+        assert_close(clock.real_delta(), 0.1);
+
+        clock.scale_time(1e-3);
+        assert_eq!(clock.time_scale, TIME_SCALE_RANGE.0);
+        clock.scale_time(1e6);
+        assert_eq!(clock.time_scale, TIME_SCALE_RANGE.1);
+    }
+
+    #[test]
+    fn paused_clock_only_steps_on_request() {
+        let mut clock = Clock::default();
+        clock.toggle_pause();
+        assert_eq!(clock.advance(0.1), 0.0);
+        assert_eq!(run_steps(&mut clock), 0);
+        // Real time still passes, for the camera
+        assert_close(clock.real_delta(), 0.1);
+
+        clock.step();
+        assert_eq!(clock.advance(0.1), FIXED_STEP);
+        assert_eq!(run_steps(&mut clock), 1);
+        assert_eq!(clock.time(), FIXED_STEP);
+
+        // A single step per request
+        assert_eq!(clock.advance(0.1), 0.0);
+        assert_eq!(run_steps(&mut clock), 0);
+    }
+
+    #[test]
+    fn step_while_running_does_nothing() {
+        let mut clock = Clock::default();
+        clock.step();
+        assert!(!clock.step_requested);
+        assert_close(clock.advance(0.01), 0.01);
+
+        // Nothing left over for when the clock is paused
+        clock.toggle_pause();
+        assert_eq!(clock.advance(0.1), 0.0);
+    }
+}
@@ -0,0 +1,153 @@
+//! Collision volumes of the scene and the queries moving the camera among them
+
+use crate::assets::bounds::Aabb;
+
+/// Shortest step a move is split into, in world units, for spheres too small to step by their radius
+const MIN_STEP: f32 = 0.01;
+
+/// Most steps a move is split into, however long it is
+const MAX_STEPS: f32 = 1000.0;
+
+/// Obstacles the camera can't go through, and the box it can't leave
+pub struct CollisionWorld {
+    /// Positions the camera is kept within
+    pub bounds: Aabb,
+    /// World space boxes around the colliding objects
+    pub colliders: Vec<Aabb>,
+}
+
+impl CollisionWorld {
+    /// Moves a sphere from `from` towards `to`, sliding along the obstacles and bounds it runs into.
+    ///
+    /// Each axis is moved separately, so that hitting an obstacle only stops the movement going into it.
+    /// A sphere already inside an obstacle, like one that moved into the camera, can only move out of it.
+    /// A radius of zero or less moves a point.
+    pub fn slide(&self, from: [f32; 3], to: [f32; 3], radius: f32) -> [f32; 3] {
+        let radius = radius.max(0.0);
+        let obstacles: Vec<Aabb> = self.colliders.iter()
+            .map(|collider| collider.expanded(radius))
+            .collect();
+
+        // Moves in steps no longer than the radius, so that fast movements can't skip over thin obstacles
+        let distance = (0..3).map(|i| (to[i] - from[i]).powi(2)).sum::<f32>().sqrt();
+        let steps = (distance / radius.max(MIN_STEP)).ceil().clamp(1.0, MAX_STEPS) as usize;
+
+        let mut position = from;
+        // Once stopped, an axis stays stopped, so that later steps can't jump over what stopped it
+        let mut stopped = [false; 3];
+        for step in 1..=steps {
+            let target = [0, 1, 2].map(|i| from[i] + (to[i] - from[i]) * step as f32 / steps as f32);
+            for axis in 0..3 {
+                if stopped[axis] {
+                    continue;
+                }
+                let mut next = position;
+                next[axis] = self.bounded(position[axis], target[axis], axis);
+                if obstacles.iter().any(|obstacle| blocks(obstacle, position, next, axis)) {
+                    stopped[axis] = true;
+                } else {
+                    position = next;
+                }
+            }
+        }
+        position
+    }
+
+    /// Where a move along `axis` from `current` to `target` ends, given the bounds.
+    ///
+    /// Moves from inside the bounds stop on their faces, moves from outside can only get closer to them.
+    fn bounded(&self, current: f32, target: f32, axis: usize) -> f32 {
+        let (min, max) = (self.bounds.min[axis], self.bounds.max[axis]);
+        if (min..=max).contains(&current) {
+            target.clamp(min, max)
+        } else if (target - target.clamp(min, max)).abs() < (current - current.clamp(min, max)).abs() {
+            target
+        } else {
+            current
+        }
+    }
+}
+
+/// Whether an obstacle stops a move along `axis`, which it does when the move ends inside of it,
+/// unless it started inside as well and goes away from its center
+fn blocks(obstacle: &Aabb, position: [f32; 3], next: [f32; 3], axis: usize) -> bool {
+    let center = obstacle.center()[axis];
+    let escaping = obstacle.contains(position) && (next[axis] - center).abs() > (position[axis] - center).abs();
+    obstacle.contains(next) && !escaping
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RADIUS: f32 = 0.1;
+
+    /// World within -10 and 10 on every axis, holding a unit box at the origin and a thin wall at x = 3
+    fn world() -> CollisionWorld {
+        CollisionWorld {
+            bounds: Aabb { min: [-10.0; 3], max: [10.0; 3] },
+            colliders: vec![
+                Aabb { min: [-0.5; 3], max: [0.5; 3] },
+                Aabb { min: [3.0, -5.0, -5.0], max: [3.01, 5.0, 5.0] },
+            ],
+        }
+    }
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        assert!((0..3).all(|i| (a[i] - b[i]).abs() < 1e-4), "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn free_moves_reach_their_target() {
+        assert_close(world().slide([-5.0, 2.0, 0.0], [-4.0, 3.0, 1.0], RADIUS), [-4.0, 3.0, 1.0]);
+    }
+
+    #[test]
+    fn diagonal_moves_slide_along_faces() {
+        // Moving diagonally into the face at z = -0.5 keeps the movement along x
+        let end = world().slide([-0.2, 0.0, -1.0], [0.2, 0.0, 0.0], RADIUS);
+        assert!((end[0] - 0.2).abs() < 1e-4, "{end:?}");
+        assert!(end[2] <= -0.5 - RADIUS + 1e-4 && end[2] > -0.7, "{end:?}");
+    }
+
+    #[test]
+    fn fast_moves_dont_go_through_thin_walls() {
+        let end = world().slide([2.0, 0.0, 0.0], [8.0, 0.0, 0.0], RADIUS);
+        assert!(end[0] < 3.0 - RADIUS + 1e-4, "{end:?}");
+    }
+
+    #[test]
+    fn starting_inside_only_moves_out() {
+        let world = world();
+        // Away from the center is allowed, towards it isn't
+        assert_close(world.slide([0.3, 0.0, 0.0], [0.4, 0.0, 0.0], RADIUS), [0.4, 0.0, 0.0]);
+        assert_close(world.slide([0.3, 0.0, 0.0], [0.1, 0.0, 0.0], RADIUS), [0.3, 0.0, 0.0]);
+        // All the way out
+        assert_close(world.slide([0.3, 0.0, 0.0], [2.0, 0.0, 0.0], RADIUS), [2.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn moves_stop_at_the_bounds() {
+        let world = world();
+        assert_close(world.slide([9.0, 0.0, 5.0], [12.0, 0.0, 5.0], RADIUS), [10.0, 0.0, 5.0]);
+        assert_close(world.slide([5.0, 9.5, 5.0], [5.0, 11.0, 6.0], RADIUS), [5.0, 10.0, 6.0]);
+    }
+
+    #[test]
+    fn moves_from_outside_the_bounds_get_closer() {
+        let world = world();
+        // Coming back is allowed, even past the faces of the bounds, going further isn't
+        assert_close(world.slide([12.0, 0.0, 5.0], [11.0, 0.0, 5.0], RADIUS), [11.0, 0.0, 5.0]);
+        assert_close(world.slide([12.0, 0.0, 5.0], [9.0, 0.0, 5.0], RADIUS), [9.0, 0.0, 5.0]);
+        assert_close(world.slide([12.0, 0.0, 5.0], [13.0, 0.0, 5.0], RADIUS), [12.0, 0.0, 5.0]);
+    }
+
+    #[test]
+    fn points_and_huge_moves_end() {
+        let world = world();
+        assert_close(world.slide([-2.0, 0.0, 0.0], [-1.0, 0.0, 0.0], 0.0), [-1.0, 0.0, 0.0]);
+        let end = world.slide([-2.0, 0.0, 0.0], [2.0, 0.0, 0.0], -1.0);
+        assert!(end[0] <= -0.5 + 1e-4, "{end:?}");
+        assert_close(world.slide([-2.0, 0.0, 0.0], [-f32::INFINITY, 0.0, 0.0], RADIUS), [-10.0, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,385 @@
+//! Named actions and the keys, mouse buttons and gamepad buttons bound to them
+
+use std::collections::BTreeMap;
+use std::{fmt, fs, io};
+
+use glium::glutin::event::{MouseButton, VirtualKeyCode};
+use serde::Deserialize;
+
+use crate::event_handler::input::InputState;
+
+/// Controls file loaded when none is given on the command line
+pub const DEFAULT_CONTROLS: &str = "controls.ron";
+
+/// Something the user can ask for through a key or button, ordered as declared
+#[derive(Deserialize, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Action {
+    /// Moves the camera forward
+    MoveForward,
+    /// Moves the camera back
+    MoveBack,
+    /// Moves the camera to the left
+    StrafeLeft,
+    /// Moves the camera to the right
+    StrafeRight,
+    /// Moves the camera up
+    MoveUp,
+    /// Moves the camera down
+    MoveDown,
+    /// Turns the camera to the left
+    TurnLeft,
+    /// Turns the camera to the right
+    TurnRight,
+    /// Tilts the camera up
+    LookUp,
+    /// Tilts the camera down
+    LookDown,
+    /// Spins the controllable objects one way
+    SpinLeft,
+    /// Spins the controllable objects the other way
+    SpinRight,
+    /// Switches to the next camera mode
+    SwitchCamera,
+    /// Moves the far clipping plane away
+    IncreaseZfar,
+    /// Brings the far clipping plane closer
+    DecreaseZfar,
+    /// Moves the near clipping plane away
+    IncreaseZnear,
+    /// Brings the near clipping plane closer
+    DecreaseZnear,
+    /// Widens the field of view
+    IncreaseFov,
+    /// Narrows the field of view
+    DecreaseFov,
+    /// Turns shadows on or off
+    ToggleShadows,
+    /// Halves the size of the shadow map
+    ShrinkShadowMap,
+    /// Doubles the size of the shadow map
+    GrowShadowMap,
+    /// Lowers the depth bias of the shadows
+    DecreaseShadowBias,
+    /// Raises the depth bias of the shadows
+    IncreaseShadowBias,
+    /// Pauses or resumes the simulation
+    Pause,
+    /// Runs a single step of the simulation while paused
+    Step,
+    /// Halves the speed of the simulation
+    SlowDown,
+    /// Doubles the speed of the simulation
+    SpeedUp,
+    /// Hides the cursor and turns the camera with the mouse
+    GrabCursor,
+    /// Releases the cursor, or grabs it again
+    ToggleCursor,
+    /// Flips the vertical axis of the mouse
+    InvertY,
+}
+
+impl Action {
+    /// Whether the action happens once per press rather than repeating while its key is held
+    pub fn is_toggle(self) -> bool {
+        matches!(self, Action::SwitchCamera | Action::ToggleShadows | Action::Pause | Action::GrabCursor | Action::ToggleCursor | Action::InvertY)
+    }
+}
+
+/// Buttons of a gamepad, named after their position on the pad
+#[derive(Deserialize, Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum GamepadButton {
+    /// Bottom face button, A on Xbox pads
+    South,
+    /// Right face button, B on Xbox pads
+    East,
+    /// Top face button, Y on Xbox pads
+    North,
+    /// Left face button, X on Xbox pads
+    West,
+    /// Left shoulder button
+    LeftTrigger,
+    /// Left trigger
+    LeftTrigger2,
+    /// Right shoulder button
+    RightTrigger,
+    /// Right trigger
+    RightTrigger2,
+    /// Button left of the center, Back or Share
+    Select,
+    /// Button right of the center, Start or Options
+    Start,
+    /// Button in the center, with the logo of the pad
+    Mode,
+    /// Pressing the left stick
+    LeftThumb,
+    /// Pressing the right stick
+    RightThumb,
+    /// Up on the directional pad
+    DPadUp,
+    /// Down on the directional pad
+    DPadDown,
+    /// Left on the directional pad
+    DPadLeft,
+    /// Right on the directional pad
+    DPadRight,
+}
+
+#[cfg(feature = "gamepad")]
+impl GamepadButton {
+    /// Button with the same position, `None` for buttons that have none of the positions above
+    pub fn from_gilrs(button: gilrs::Button) -> Option<GamepadButton> {
+        use gilrs::Button;
+        Some(match button {
+            Button::South => GamepadButton::South,
+            Button::East => GamepadButton::East,
+            Button::North => GamepadButton::North,
+            Button::West => GamepadButton::West,
+            Button::LeftTrigger => GamepadButton::LeftTrigger,
+            Button::LeftTrigger2 => GamepadButton::LeftTrigger2,
+            Button::RightTrigger => GamepadButton::RightTrigger,
+            Button::RightTrigger2 => GamepadButton::RightTrigger2,
+            Button::Select => GamepadButton::Select,
+            Button::Start => GamepadButton::Start,
+            Button::Mode => GamepadButton::Mode,
+            Button::LeftThumb => GamepadButton::LeftThumb,
+            Button::RightThumb => GamepadButton::RightThumb,
+            Button::DPadUp => GamepadButton::DPadUp,
+            Button::DPadDown => GamepadButton::DPadDown,
+            Button::DPadLeft => GamepadButton::DPadLeft,
+            Button::DPadRight => GamepadButton::DPadRight,
+            _ => return None,
+        })
+    }
+}
+
+/// A key or button an action can be bound to
+#[derive(Deserialize, Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Binding {
+    /// A key of the keyboard, by its meaning in the current layout
+    Key(VirtualKeyCode),
+    /// A button of the mouse
+    Mouse(MouseButton),
+    /// A button of a gamepad
+    Gamepad(GamepadButton),
+}
+
+/// Maps each action to the keys and buttons triggering it
+#[derive(PartialEq, Debug)]
+pub struct ActionMap {
+    bindings: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl Default for ActionMap {
+    /// The layout the scene has always had, with the gamepad moving and turning the camera
+    fn default() -> Self {
+        use Binding::{Gamepad, Key, Mouse};
+        use GamepadButton as Pad;
+
+        let bindings = [
+            (Action::MoveForward, vec![Key(VirtualKeyCode::W), Gamepad(Pad::DPadUp)]),
+            (Action::MoveBack, vec![Key(VirtualKeyCode::S), Gamepad(Pad::DPadDown)]),
+            (Action::StrafeLeft, vec![Key(VirtualKeyCode::A), Gamepad(Pad::DPadLeft)]),
+            (Action::StrafeRight, vec![Key(VirtualKeyCode::D), Gamepad(Pad::DPadRight)]),
+            (Action::MoveUp, vec![Key(VirtualKeyCode::Space), Gamepad(Pad::South)]),
+            (Action::MoveDown, vec![Key(VirtualKeyCode::LShift), Gamepad(Pad::East)]),
+            (Action::TurnLeft, vec![Key(VirtualKeyCode::Left), Gamepad(Pad::LeftTrigger)]),
+            (Action::TurnRight, vec![Key(VirtualKeyCode::Right), Gamepad(Pad::RightTrigger)]),
+            (Action::LookUp, vec![Key(VirtualKeyCode::Up), Gamepad(Pad::North)]),
+            (Action::LookDown, vec![Key(VirtualKeyCode::Down), Gamepad(Pad::West)]),
+            (Action::SpinLeft, vec![Key(VirtualKeyCode::J)]),
+            (Action::SpinRight, vec![Key(VirtualKeyCode::K)]),
+            (Action::SwitchCamera, vec![Key(VirtualKeyCode::C), Gamepad(Pad::Select)]),
+            (Action::IncreaseZfar, vec![Key(VirtualKeyCode::F1)]),
+            (Action::DecreaseZfar, vec![Key(VirtualKeyCode::F2)]),
+            (Action::IncreaseZnear, vec![Key(VirtualKeyCode::F3)]),
+            (Action::DecreaseZnear, vec![Key(VirtualKeyCode::F4)]),
+            (Action::IncreaseFov, vec![Key(VirtualKeyCode::F5)]),
+            (Action::DecreaseFov, vec![Key(VirtualKeyCode::F6)]),
+            (Action::ToggleShadows, vec![Key(VirtualKeyCode::F7)]),
+            (Action::ShrinkShadowMap, vec![Key(VirtualKeyCode::F8)]),
+            (Action::GrowShadowMap, vec![Key(VirtualKeyCode::F9)]),
+            (Action::DecreaseShadowBias, vec![Key(VirtualKeyCode::F10)]),
+            (Action::IncreaseShadowBias, vec![Key(VirtualKeyCode::F11)]),
+            (Action::Pause, vec![Key(VirtualKeyCode::P), Gamepad(Pad::Start)]),
+            (Action::Step, vec![Key(VirtualKeyCode::N)]),
+            (Action::SlowDown, vec![Key(VirtualKeyCode::Comma)]),
+            (Action::SpeedUp, vec![Key(VirtualKeyCode::Period)]),
+            (Action::GrabCursor, vec![Mouse(MouseButton::Left)]),
+            (Action::ToggleCursor, vec![Key(VirtualKeyCode::Escape)]),
+            (Action::InvertY, vec![Key(VirtualKeyCode::I)]),
+        ];
+        ActionMap { bindings: bindings.into_iter().collect() }
+    }
+}
+
+impl ActionMap {
+    /// Actions triggered by a key or button, in the order they are declared
+    pub fn actions(&self, binding: Binding) -> Vec<Action> {
+        self.bindings.iter()
+            .filter(|(_, bindings)| bindings.contains(&binding))
+            .map(|(action, _)| *action)
+            .collect()
+    }
+
+    /// Whether any key or button bound to the action is held
+    pub fn is_held(&self, input: &InputState, action: Action) -> bool {
+        self.bindings.get(&action).is_some_and(|bindings| bindings.iter().any(|binding| input.is_held(*binding)))
+    }
+
+    /// 1 when only `positive` is held, -1 when only `negative` is, 0 otherwise
+    pub fn axis(&self, input: &InputState, positive: Action, negative: Action) -> f32 {
+        self.is_held(input, positive) as i32 as f32 - self.is_held(input, negative) as i32 as f32
+    }
+}
+
+/// Errors that can happen while loading a controls file
+#[derive(Debug)]
+pub enum ControlsError {
+    /// The controls file could not be read
+    Io(io::Error),
+    /// The controls file is not a valid controls description
+    Parse(ron::error::SpannedError),
+}
+
+impl fmt::Display for ControlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlsError::Io(e) => write!(f, "could not read controls file: {e}"),
+            ControlsError::Parse(e) => write!(f, "invalid controls file: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ControlsError {}
+
+impl From<io::Error> for ControlsError {
+    fn from(e: io::Error) -> Self {
+        ControlsError::Io(e)
+    }
+}
+
+impl From<ron::error::SpannedError> for ControlsError {
+    fn from(e: ron::error::SpannedError) -> Self {
+        ControlsError::Parse(e)
+    }
+}
+
+/// Description of the controls, as written in a controls file
+#[derive(Deserialize)]
+pub struct ControlsDescription {
+    /// Bindings replacing the default ones of the actions listed, other actions keep their default bindings
+    #[serde(default)]
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+    /// Angle, in radians, the camera turns by for each pixel the mouse moves
+    #[serde(default = "default_mouse_sensitivity")]
+    pub mouse_sensitivity: f32,
+    /// Whether moving the mouse up makes the camera look down
+    #[serde(default)]
+    pub invert_y: bool,
+}
+
+fn default_mouse_sensitivity() -> f32 {
+    0.002
+}
+
+/// The bindings and mouse settings in use
+pub struct Controls {
+    /// Keys and buttons bound to each action
+    pub actions: ActionMap,
+    /// Angle, in radians, the camera turns by for each pixel the mouse moves
+    pub mouse_sensitivity: f32,
+    /// Whether moving the mouse up makes the camera look down
+    pub invert_y: bool,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Controls {
+            actions: ActionMap::default(),
+            mouse_sensitivity: default_mouse_sensitivity(),
+            invert_y: false,
+        }
+    }
+}
+
+impl Controls {
+    /// Reads a controls file, on top of the default bindings
+    pub fn load(path: &str) -> Result<Controls, ControlsError> {
+        let description: ControlsDescription = ron::from_str(&fs::read_to_string(path)?)?;
+
+        let mut actions = ActionMap::default();
+        actions.bindings.extend(description.bindings);
+
+        Ok(Controls {
+            actions,
+            mouse_sensitivity: description.mouse_sensitivity,
+            invert_y: description.invert_y,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// Writes `contents` to a file of the temporary directory, unique to this process, and returns its path
+    fn temp_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("ogl_house_scene_{}_{name}", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn load(name: &str, contents: &str) -> Result<Controls, ControlsError> {
+        let path = temp_file(name, contents);
+        let controls = Controls::load(path.to_str().unwrap());
+        fs::remove_file(&path).ok();
+        controls
+    }
+
+    #[test]
+    fn controls_file_lists_the_defaults() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/controls.ron");
+        let description: ControlsDescription = ron::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+
+        assert_eq!(ActionMap { bindings: description.bindings }, ActionMap::default());
+        assert_eq!(description.mouse_sensitivity, default_mouse_sensitivity());
+        assert!(!description.invert_y);
+    }
+
+    #[test]
+    fn partial_file_overrides_named_actions() {
+        let controls = load("partial_controls.ron", "(bindings: { MoveForward: [Key(Up)] }, invert_y: true)").unwrap();
+
+        let mut expected = ActionMap::default();
+        expected.bindings.insert(Action::MoveForward, vec![Binding::Key(VirtualKeyCode::Up)]);
+        assert_eq!(controls.actions, expected);
+        assert_eq!(controls.mouse_sensitivity, default_mouse_sensitivity());
+        assert!(controls.invert_y);
+
+        // W is left unbound, Up now triggers both actions
+        assert_eq!(controls.actions.actions(Binding::Key(VirtualKeyCode::W)), []);
+        assert_eq!(controls.actions.actions(Binding::Key(VirtualKeyCode::Up)), [Action::MoveForward, Action::LookUp]);
+    }
+
+    #[test]
+    fn actions_in_declaration_order() {
+        let mut map = ActionMap::default();
+        let space = Binding::Key(VirtualKeyCode::Space);
+        for action in [Action::InvertY, Action::Pause, Action::MoveForward] {
+            map.bindings.insert(action, vec![space]);
+        }
+
+        assert_eq!(map.actions(space), [Action::MoveForward, Action::MoveUp, Action::Pause, Action::InvertY]);
+    }
+
+    #[test]
+    fn malformed_file_is_an_error() {
+        let result = load("malformed_controls.ron", "(bindings: { MoveForward: [Key(NotAKey)] })");
+        assert!(matches!(result, Err(ControlsError::Parse(_))));
+
+        let result = Controls::load("no/such/controls.ron");
+        assert!(matches!(result, Err(ControlsError::Io(_))));
+    }
+}
@@ -0,0 +1,75 @@
+//! State of the keys and buttons, tracked across the events pressing and releasing them
+
+use std::collections::HashSet;
+
+use glium::glutin::event::ElementState;
+
+use crate::event_handler::actions::Binding;
+
+/// Keeps track of which keys and buttons are held down
+#[derive(Default)]
+pub struct InputState {
+    held: HashSet<Binding>,
+}
+
+impl InputState {
+    /// Records a key or button being pressed or released, returns whether it was just pressed rather than repeated
+    pub fn update(&mut self, binding: Binding, state: ElementState) -> bool {
+        match state {
+            ElementState::Pressed => self.held.insert(binding),
+            ElementState::Released => {
+                self.held.remove(&binding);
+                false
+            }
+        }
+    }
+
+    /// Whether a key or button was pressed and has not been released since
+    pub fn is_held(&self, binding: Binding) -> bool {
+        self.held.contains(&binding)
+    }
+
+    /// Releases every key and button, for when the window stops receiving their release events
+    pub fn clear(&mut self) {
+        self.held.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glium::glutin::event::{MouseButton, VirtualKeyCode};
+
+    use super::*;
+
+    #[test]
+    fn first_press_only() {
+        let mut input = InputState::default();
+        let key = Binding::Key(VirtualKeyCode::W);
+
+        assert!(input.update(key, ElementState::Pressed));
+        assert!(input.is_held(key));
+        // Key repeat sends presses without releases in between
+        assert!(!input.update(key, ElementState::Pressed));
+        assert!(input.is_held(key));
+
+        assert!(!input.update(key, ElementState::Released));
+        assert!(!input.is_held(key));
+        assert!(input.update(key, ElementState::Pressed));
+    }
+
+    #[test]
+    fn bindings_are_independent() {
+        let mut input = InputState::default();
+        let key = Binding::Key(VirtualKeyCode::W);
+        let button = Binding::Mouse(MouseButton::Left);
+
+        input.update(key, ElementState::Pressed);
+        input.update(button, ElementState::Pressed);
+        input.update(key, ElementState::Released);
+        assert!(!input.is_held(key));
+        assert!(input.is_held(button));
+
+        input.clear();
+        assert!(!input.is_held(button));
+    }
+}
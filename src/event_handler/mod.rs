@@ -1,81 +1,189 @@
-use std::f32::consts::PI;
-use glium::glutin::event::{ElementState, Event, VirtualKeyCode, WindowEvent};
+//! Input handling, turning window events into camera motion and settings changes
+
+use glium::glutin::event::{DeviceEvent, ElementState, Event, MouseScrollDelta, WindowEvent};
 use glium::glutin::event_loop::ControlFlow;
 
-use crate::{glutin, rotate, translate};
-use crate::glutin::event::KeyboardInput;
+use crate::camera::{Camera, CameraMode, Controller, FOV_RANGE};
+use crate::clock::Clock;
+use crate::collision::CollisionWorld;
+use glium::glutin;
+use glium::glutin::event::KeyboardInput;
+use crate::model::shadow::ShadowSettings;
+use actions::{Action, Binding, Controls};
+use input::InputState;
 
-fn normalize_vector(vector: &[f32; 3]) -> [f32; 3] {
-    let length = (vector[0] * vector[0] + vector[1] * vector[1] + vector[2] * vector[2]).sqrt();
-    [
-        vector[0] / length,
-        vector[1] / length,
-        vector[2] / length,
-    ]
-}
+pub mod actions;
+mod input;
 
-fn cross_product(a: &[f32; 3], b: &[f32; 3]) -> [f32; 3] {
-    [
-        a[1] * b[2] - a[2] * b[1],
-        a[2] * b[0] - a[0] * b[2],
-        a[0] * b[1] - a[1] * b[0],
-    ]
-}
+/// Top speed of the camera, in world units per second
+const MOVE_SPEED: f32 = 1.0;
 
-fn add_vectors(a: &[f32; 3], b: &[f32; 3]) -> [f32; 3] {
-    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
-}
+/// How fast the camera gets to its top speed and back to a stop, in world units per second squared
+const ACCELERATION: f32 = 8.0;
 
-fn sub_vectors(a: &[f32; 3], b: &[f32; 3]) -> [f32; 3] {
-    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
-}
+/// Angular speed of the camera while a turn action is held, in radians per second
+const TURN_SPEED: f32 = 1.5;
 
-fn vec_scal_mul(vector: &[f32; 3], scalar: f32) -> [f32; 3] {
-    [vector[0] * scalar, vector[1] * scalar, vector[2] * scalar]
-}
+/// Angular speed of the controllable objects while a spin action is held, in radians per second
+const SPIN_SPEED: f32 = 1.5;
 
-/// Function that returns if a position is inside the bounds of the scene
-fn is_inbounds(position: &[f32; 3]) -> bool {
-    return position[0] >= -2.2 && position[0] <= 2.2 && position[1] >= 0.1 && position[1] <= 2.2 && position[2] >= -2.2 && position[2] <= 2.2;
-}
+/// Pixels of a touchpad scroll that count as a scrolled line
+const PIXELS_PER_LINE: f32 = 20.0;
 
 /// Struct that handles the events of the window.
 pub struct EventHandler {
+    /// Scale of the controllable objects, between 0.01 and 1
     pub grow: f32,
+    /// Tilt of the controllable objects, in radians, between -1 and 1
     pub tilt: f32,
+    /// Spin of the controllable objects around the x axis, in radians
     pub spin: f32,
+    /// Offset of the controllable objects along the x axis
     pub translate_x: f32,
+    /// Offset of the controllable objects along the y axis
     pub translate_y: f32,
-    pub zfar: f32,
-    pub znear: f32,
-    pub fov: f32,
-    pub direction: [f32; 3],
-    pub position: [f32; 3],
-    pub up: [f32; 3],
+    /// Camera the scene is rendered from
+    pub camera: Camera,
+    /// Mode of `controller`
+    pub camera_mode: CameraMode,
+    /// Moves the camera according to `camera_mode`
+    pub controller: Box<dyn Controller>,
+    /// Whether the cursor is hidden and locked to the window, turning the camera when the mouse moves
+    pub cursor_grabbed: bool,
+    /// Bindings of the actions and mouse settings
+    pub controls: Controls,
+    /// Keys and buttons held down, moving the camera on every update
+    pub input: InputState,
+    /// Velocity of the camera along its forward, right and up axes, in world units per second
+    pub velocity: [f32; 3],
+    /// Shadow settings, changed by the shadow actions
+    pub shadows: ShadowSettings,
+    /// Clock of the simulation, changed by the time actions
+    pub clock: Clock,
 }
 
 impl EventHandler {
-    pub fn new(grow: f32, tilt: f32, spin: f32, translate_x: f32, translate_y: f32, zfar: f32, znear: f32, fov: f32,
-               direction: [f32; 3], position: [f32; 3], up: [f32; 3]) -> Self {
-        EventHandler {grow, tilt, spin, translate_x, translate_y, zfar, znear, fov, direction, position, up}
+    /// Applies the held keys, `delta` seconds after the previous update, keeping the camera out of the obstacles of `world`
+    pub fn update(&mut self, delta: f32, world: &CollisionWorld) {
+        let EventHandler { ref mut spin, ref mut camera, ref mut controller, ref controls, ref input, ref mut velocity, .. } = *self;
+        let actions = &controls.actions;
+
+        // Diagonals are normalized so that they are no faster than moving along a single axis
+        let wish = [
+            actions.axis(input, Action::MoveForward, Action::MoveBack),
+            actions.axis(input, Action::StrafeRight, Action::StrafeLeft),
+            actions.axis(input, Action::MoveUp, Action::MoveDown),
+        ];
+        let wish_length = wish.iter().map(|w| w * w).sum::<f32>().sqrt().max(1.0);
+        let target = wish.map(|w| w / wish_length * MOVE_SPEED);
+
+        // Accelerates towards the target velocity, slowing down the same way once the keys are released
+        let difference = [0, 1, 2].map(|i| target[i] - velocity[i]);
+        let distance = difference.iter().map(|d| d * d).sum::<f32>().sqrt();
+        let step = ACCELERATION * delta;
+        *velocity = if distance <= step {
+            target
+        } else {
+            [0, 1, 2].map(|i| velocity[i] + difference[i] / distance * step)
+        };
+
+        if *velocity != [0.0; 3] {
+            controller.move_by(camera, world, velocity[0] * delta, velocity[1] * delta, velocity[2] * delta);
+        }
+
+        let turn = TURN_SPEED * delta;
+        let yaw = actions.axis(input, Action::TurnRight, Action::TurnLeft);
+        let pitch = actions.axis(input, Action::LookUp, Action::LookDown);
+        if yaw != 0.0 || pitch != 0.0 {
+            controller.turn(camera, yaw * turn, pitch * turn);
+        }
+
+        *spin += actions.axis(input, Action::SpinLeft, Action::SpinRight) * SPIN_SPEED * delta;
     }
 
-    /// Method that handles the keyboard input
-    pub fn handle_event(&mut self, ev: Event<()>, cf: &mut ControlFlow) {
+    /// Records a key or button being pressed or released and triggers the actions bound to it.
+    ///
+    /// Held actions like movement are only applied on the next update. Adjustments repeat along with the key,
+    /// toggles only happen once per press.
+    pub fn handle_binding(&mut self, binding: Binding, state: ElementState) {
+        let just_pressed = self.input.update(binding, state);
+        if state != ElementState::Pressed {
+            return;
+        }
+        for action in self.controls.actions.actions(binding) {
+            if just_pressed || !action.is_toggle() {
+                self.trigger(action);
+            }
+        }
+    }
 
+    /// Applies an action that happens on press rather than while held
+    fn trigger(&mut self, action: Action) {
         let EventHandler {
-            ref mut grow,
-            ref mut tilt,
-            ref mut spin,
-            ref mut translate_x,
-            ref mut translate_y,
-            ref mut zfar,
-            ref mut znear,
-            ref mut fov,
-            ref mut direction,
-            ref mut position,
-            ref mut up,
-        } = self;
+            ref mut camera,
+            ref mut camera_mode,
+            ref mut controller,
+            ref mut cursor_grabbed,
+            ref mut controls,
+            ref mut shadows,
+            ref mut clock,
+            ..
+        } = *self;
+
+        match action {
+            Action::SwitchCamera => {
+                *camera_mode = camera_mode.next();
+                *controller = camera_mode.controller();
+                controller.attach(camera);
+            }
+            Action::IncreaseZfar => {
+                camera.zfar += 0.4;
+            },
+            Action::DecreaseZfar => {
+                camera.zfar -= 0.4;
+            },
+            Action::IncreaseZnear => {
+                camera.znear += 0.1;
+            },
+            Action::DecreaseZnear => {
+                camera.znear -= 0.1;
+            },
+            Action::IncreaseFov => {
+                camera.fov = (camera.fov + 0.01).min(FOV_RANGE.1);
+            },
+            Action::DecreaseFov => {
+                camera.fov = (camera.fov - 0.01).max(FOV_RANGE.0);
+            },
+            Action::ToggleShadows => {
+                shadows.enabled = !shadows.enabled;
+            },
+            Action::ShrinkShadowMap => {
+                shadows.resolution = (shadows.resolution / 2).max(256);
+            },
+            Action::GrowShadowMap => {
+                shadows.resolution = (shadows.resolution * 2).min(8192);
+            },
+            Action::DecreaseShadowBias => {
+                shadows.bias = (shadows.bias - 0.0001).max(0.0);
+            },
+            Action::IncreaseShadowBias => {
+                shadows.bias += 0.0001;
+            },
+            Action::Pause => clock.toggle_pause(),
+            Action::Step => clock.step(),
+            Action::SlowDown => clock.scale_time(0.5),
+            Action::SpeedUp => clock.scale_time(2.0),
+            Action::GrabCursor => *cursor_grabbed = true,
+            Action::ToggleCursor => *cursor_grabbed = !*cursor_grabbed,
+            Action::InvertY => controls.invert_y = !controls.invert_y,
+            // Held actions, applied by `update`
+            Action::MoveForward | Action::MoveBack | Action::StrafeLeft | Action::StrafeRight | Action::MoveUp | Action::MoveDown |
+            Action::TurnLeft | Action::TurnRight | Action::LookUp | Action::LookDown | Action::SpinLeft | Action::SpinRight => (),
+        }
+    }
+
+    /// Method that handles the keyboard input
+    pub fn handle_event(&mut self, ev: Event<()>, cf: &mut ControlFlow) {
 
         match ev {
             glutin::event::Event::WindowEvent { event, .. } => match event {
@@ -90,105 +198,53 @@ impl EventHandler {
                     } else {
                         return;
                     };
-                    const STEP: f32 = 0.05;
-                    /// If the key is pressed, the value is changed
-                    if let state = ElementState::Pressed {
-                        let camera_facing = normalize_vector(&sub_vectors(&direction, &position));
-                        let camera_facing_orth = normalize_vector(&cross_product(&camera_facing, &up));
-                        let camera_vert_vec = normalize_vector(&cross_product(&camera_facing_orth, &camera_facing));
-                        println!("pos: {:?}, dir: {:?}", position, direction);
-                        /// Parses the pressed key and changes the value
-                        match virtual_keycode {
-                            VirtualKeyCode::W => {
-                                let new_position = add_vectors(&position, &vec_scal_mul(&camera_facing, STEP));
-                                if is_inbounds(&new_position) {
-                                    *position = new_position;
-                                    *direction = add_vectors(&direction, &vec_scal_mul(&camera_facing, STEP));
-                                }
-                            },
-                            VirtualKeyCode::A => {
-                                let new_position = add_vectors(&position, &vec_scal_mul(&camera_facing_orth, STEP));
-                                if is_inbounds(&new_position) {
-                                    *position = new_position;
-                                    *direction = add_vectors(&direction, &vec_scal_mul(&camera_facing_orth, STEP));
-                                }
-                            }
-                            VirtualKeyCode::S => {
-                                let new_position = sub_vectors(&position, &vec_scal_mul(&camera_facing, STEP));
-                                if is_inbounds(&new_position) {
-                                    *position = new_position;
-                                    *direction = sub_vectors(&direction, &vec_scal_mul(&camera_facing, STEP));
-                                }
-                            }
-                            VirtualKeyCode::D => {
-                                let new_position = sub_vectors(&position, &vec_scal_mul(&camera_facing_orth, STEP));
-                                if is_inbounds(&new_position) {
-                                    *position = new_position;
-                                    *direction = sub_vectors(&direction, &vec_scal_mul(&camera_facing_orth, STEP));
-                                }
-                            }
-                            VirtualKeyCode::J => *spin += STEP,
-                            VirtualKeyCode::K => *spin -= STEP,
-                            VirtualKeyCode::Right => {
-                                *direction = sub_vectors(&direction, &camera_facing_orth);
-                            }
-                            VirtualKeyCode::Left => {
-                                *direction = add_vectors(&direction, &camera_facing_orth);
-                            }
-                            VirtualKeyCode::Up => {
-                                *direction = add_vectors(&direction, &camera_vert_vec);
-                            }
-                            VirtualKeyCode::Down => {
-                                *direction = sub_vectors(&direction, &camera_vert_vec);
-                            },
-                            VirtualKeyCode::F1 => {
-                                *zfar += 0.4;
-                            },
-                            VirtualKeyCode::F2 => {
-                                *zfar -= 0.4;
-                            },
-                            VirtualKeyCode::F3 => {
-                                *znear += 0.1;
-                            },
-                            VirtualKeyCode::F4 => {
-                                *znear -= 0.1;
-                            },
-                            VirtualKeyCode::F5 => {
-                                *fov += 0.01;
-                            },
-                            VirtualKeyCode::F6 => {
-                                *fov -= 0.01;
-                            },
-                            _ => (),
-                        }
-                    }
+                    self.handle_binding(Binding::Key(virtual_keycode), state);
 
-                    if *grow < 0.02 {
-                        *grow = 0.01;
-                    } else if *grow > 1.0 {
-                        *grow = 1.0;
+                    if self.grow < 0.02 {
+                        self.grow = 0.01;
+                    } else if self.grow > 1.0 {
+                        self.grow = 1.0;
                     }
 
-                    if *tilt > 1.0 {
-                        *tilt = 1.0;
-                    } else if *tilt < -1.0 {
-                        *tilt = -1.0;
-                    }
+                    self.tilt = self.tilt.clamp(-1.0, 1.0);
+
+                }
+
+                WindowEvent::MouseInput { state, button, .. } => {
+                    self.handle_binding(Binding::Mouse(button), state);
+                }
+
+                WindowEvent::MouseWheel { delta, .. } => {
+                    let lines = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => y,
+                        MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_LINE,
+                    };
+                    self.controller.zoom(&mut self.camera, lines);
+                }
 
+                // Gives the cursor back when switching to another window, which also gets the key releases
+                WindowEvent::Focused(false) => {
+                    self.cursor_grabbed = false;
+                    self.input.clear();
                 }
 
                 glutin::event::WindowEvent::CloseRequested => {
                     *cf = glutin::event_loop::ControlFlow::Exit;
-                    return;
                 }
-                _ => return,
+                _ => (),
             },
+            // Raw motion keeps coming while the cursor is locked against the border of the window
+            glutin::event::Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta: (dx, dy) }, .. } if self.cursor_grabbed => {
+                let Controls { mouse_sensitivity, invert_y, .. } = self.controls;
+                let dy = if invert_y { -dy } else { dy };
+                self.controller.turn(&mut self.camera, dx as f32 * mouse_sensitivity, -dy as f32 * mouse_sensitivity);
+            }
             glutin::event::Event::NewEvents(cause) => match cause {
                 glutin::event::StartCause::ResumeTimeReached { .. } => (),
                 glutin::event::StartCause::Init => (),
-                _ => return,
+                _ => (),
             },
-            _ => return,
+            _ => (),
         }
     }
 }
@@ -201,12 +257,77 @@ impl Default for EventHandler {
             spin: 0.0,
             translate_x: 0.0,
             translate_y: 0.0,
-            direction: [0.0, 0.0, 1.0],
-            position: [0.0, 0.1, 0.0],
-            up: [0.0, 1.0, 0.0],
-            zfar: 30.0,
-            znear: 0.1,
-            fov: PI / 3.0,
+            camera: Camera::default(),
+            camera_mode: CameraMode::default(),
+            controller: CameraMode::default().controller(),
+            cursor_grabbed: false,
+            controls: Controls::default(),
+            input: InputState::default(),
+            velocity: [0.0; 3],
+            shadows: ShadowSettings::default(),
+            clock: Clock::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glium::glutin::event::VirtualKeyCode;
+
+    use super::*;
+    use crate::assets::bounds::Aabb;
+
+    /// Open space, nothing to collide with
+    fn world() -> CollisionWorld {
+        CollisionWorld { bounds: Aabb { min: [-100.0; 3], max: [100.0; 3] }, colliders: Vec::new() }
+    }
+
+    fn press(handler: &mut EventHandler, key: VirtualKeyCode, state: ElementState) {
+        handler.handle_binding(Binding::Key(key), state);
+    }
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        assert!((0..3).all(|i| (a[i] - b[i]).abs() < 1e-5), "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn diagonals_are_no_faster() {
+        let mut handler = EventHandler::default();
+        press(&mut handler, VirtualKeyCode::W, ElementState::Pressed);
+        press(&mut handler, VirtualKeyCode::A, ElementState::Pressed);
+
+        // Long enough to reach the top speed at once
+        handler.update(1.0, &world());
+        let diagonal = MOVE_SPEED / 2.0f32.sqrt();
+        assert_close(handler.velocity, [diagonal, -diagonal, 0.0]);
+        assert_close(handler.camera.position, [-diagonal, 0.1, diagonal]);
+    }
+
+    #[test]
+    fn velocity_ramps_up_and_down() {
+        const DELTA: f32 = 0.05;
+        let step = ACCELERATION * DELTA;
+        let mut handler = EventHandler::default();
+        press(&mut handler, VirtualKeyCode::W, ElementState::Pressed);
+
+        handler.update(DELTA, &world());
+        assert_close(handler.velocity, [step, 0.0, 0.0]);
+        handler.update(DELTA, &world());
+        assert_close(handler.velocity, [2.0 * step, 0.0, 0.0]);
+        // Capped at the top speed
+        handler.update(DELTA, &world());
+        assert_close(handler.velocity, [MOVE_SPEED, 0.0, 0.0]);
+
+        press(&mut handler, VirtualKeyCode::W, ElementState::Released);
+        handler.update(DELTA, &world());
+        assert_close(handler.velocity, [MOVE_SPEED - step, 0.0, 0.0]);
+        for _ in 0..3 {
+            handler.update(DELTA, &world());
         }
+        // Comes to a complete stop rather than creeping on
+        assert_eq!(handler.velocity, [0.0; 3]);
+        let position = handler.camera.position;
+        handler.update(DELTA, &world());
+        assert_eq!(handler.camera.position, position);
     }
 }
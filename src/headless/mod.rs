@@ -0,0 +1,235 @@
+//! Offscreen rendering, for machines without a display or a GPU.
+//!
+//! The OpenGL context is created through EGL on its surfaceless platform, which Mesa provides along with
+//! its software renderer. libEGL is loaded when a context is created, so that building doesn't require it.
+
+use std::ffi::c_void;
+use std::fmt;
+use std::rc::Rc;
+
+use glium::backend::{Backend, Context, Facade};
+use glium::framebuffer::{DepthRenderBuffer, RenderBufferCreationError, SimpleFrameBuffer, ValidationError};
+use glium::texture::{DepthFormat, MipmapsOption, RawImage2d, SrgbFormat, SrgbTexture2d, TextureCreationError};
+use glium::{IncompatibleOpenGl, ProgramCreationError, SwapBuffersError};
+use image::{imageops, RgbaImage};
+use khronos_egl as egl;
+
+use crate::app::LIGHT_ROTATION_SPEED;
+use crate::camera::Camera;
+use crate::model::renderer::Renderer;
+use crate::scene::{Scene, SceneError, SceneObject};
+
+/// `EGL_PLATFORM_SURFACELESS_MESA`, which needs neither a window system nor a GPU
+const PLATFORM_SURFACELESS: egl::Enum = 0x31DD;
+
+/// Size, in pixels, of the images rendered when none is given
+pub const DEFAULT_SIZE: (u32, u32) = (800, 600);
+
+/// Errors that can happen while rendering offscreen
+#[derive(Debug)]
+pub enum HeadlessError {
+    /// libEGL is missing, or could not create an OpenGL context
+    Egl(String),
+    /// The OpenGL context doesn't support what glium needs
+    Context(IncompatibleOpenGl),
+    /// The shaders did not compile
+    Program(ProgramCreationError),
+    /// The scene could not be loaded
+    Scene(SceneError),
+    /// The image would have no pixels, its width or height being zero
+    Size(u32, u32),
+    /// The color buffer could not be created
+    Texture(TextureCreationError),
+    /// The depth buffer could not be created
+    DepthBuffer(RenderBufferCreationError),
+    /// The color and depth buffers could not be attached to a framebuffer
+    Framebuffer(ValidationError),
+    /// The pixels read back from the color buffer don't fill an image of its size
+    Readback,
+    /// The image could not be written
+    Image(image::ImageError),
+}
+
+impl fmt::Display for HeadlessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeadlessError::Egl(e) => write!(f, "could not create an offscreen context: {e}"),
+            HeadlessError::Context(e) => write!(f, "unsupported offscreen context: {e:?}"),
+            HeadlessError::Program(e) => write!(f, "could not compile shaders: {e}"),
+            HeadlessError::Scene(e) => write!(f, "{e}"),
+            HeadlessError::Size(width, height) => write!(f, "cannot render an image of {width}x{height} pixels"),
+            HeadlessError::Texture(e) => write!(f, "could not create the color buffer: {e}"),
+            HeadlessError::DepthBuffer(e) => write!(f, "could not create the depth buffer: {e:?}"),
+            HeadlessError::Framebuffer(e) => write!(f, "could not create the framebuffer: {e:?}"),
+            HeadlessError::Readback => write!(f, "could not read the rendered image back"),
+            HeadlessError::Image(e) => write!(f, "could not write image: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for HeadlessError {}
+
+impl From<egl::Error> for HeadlessError {
+    fn from(e: egl::Error) -> Self {
+        HeadlessError::Egl(e.to_string())
+    }
+}
+
+impl From<IncompatibleOpenGl> for HeadlessError {
+    fn from(e: IncompatibleOpenGl) -> Self {
+        HeadlessError::Context(e)
+    }
+}
+
+impl From<ProgramCreationError> for HeadlessError {
+    fn from(e: ProgramCreationError) -> Self {
+        HeadlessError::Program(e)
+    }
+}
+
+impl From<SceneError> for HeadlessError {
+    fn from(e: SceneError) -> Self {
+        HeadlessError::Scene(e)
+    }
+}
+
+impl From<TextureCreationError> for HeadlessError {
+    fn from(e: TextureCreationError) -> Self {
+        HeadlessError::Texture(e)
+    }
+}
+
+impl From<RenderBufferCreationError> for HeadlessError {
+    fn from(e: RenderBufferCreationError) -> Self {
+        HeadlessError::DepthBuffer(e)
+    }
+}
+
+impl From<ValidationError> for HeadlessError {
+    fn from(e: ValidationError) -> Self {
+        HeadlessError::Framebuffer(e)
+    }
+}
+
+impl From<image::ImageError> for HeadlessError {
+    fn from(e: image::ImageError) -> Self {
+        HeadlessError::Image(e)
+    }
+}
+
+type Egl = egl::DynamicInstance<egl::EGL1_5>;
+
+/// OpenGL context without any surface, everything is drawn into framebuffers
+struct EglBackend {
+    egl: Egl,
+    display: egl::Display,
+    context: egl::Context,
+}
+
+unsafe impl Backend for EglBackend {
+    fn swap_buffers(&self) -> Result<(), SwapBuffersError> {
+        Ok(())
+    }
+
+    unsafe fn get_proc_address(&self, symbol: &str) -> *const c_void {
+        self.egl.get_proc_address(symbol).map_or(std::ptr::null(), |f| f as *const c_void)
+    }
+
+    /// There is no default framebuffer to speak of
+    fn get_framebuffer_dimensions(&self) -> (u32, u32) {
+        (1, 1)
+    }
+
+    fn is_current(&self) -> bool {
+        self.egl.get_current_context() == Some(self.context)
+    }
+
+    unsafe fn make_current(&self) {
+        if let Err(e) = self.egl.make_current(self.display, None, None, Some(self.context)) {
+            eprintln!("Could not make the offscreen context current: {e}");
+        }
+    }
+}
+
+impl Drop for EglBackend {
+    fn drop(&mut self) {
+        // Nothing more can be done about failures while tearing down
+        self.egl.make_current(self.display, None, None, None).ok();
+        self.egl.destroy_context(self.display, self.context).ok();
+        self.egl.terminate(self.display).ok();
+    }
+}
+
+/// Creates an OpenGL 3.3 core context that renders offscreen, on Mesa's software renderer when there is no GPU
+pub fn headless_context() -> Result<Rc<Context>, HeadlessError> {
+    // Safety: libEGL is trusted to implement the EGL API it claims to
+    let egl = unsafe { Egl::load_required() }.map_err(|e| HeadlessError::Egl(e.to_string()))?;
+
+    let display = egl.get_platform_display(PLATFORM_SURFACELESS, egl::DEFAULT_DISPLAY, &[egl::ATTRIB_NONE])?;
+    egl.initialize(display)?;
+    egl.bind_api(egl::OPENGL_API)?;
+
+    // Configurations default to window surfaces, which the surfaceless platform has none of
+    let config = egl.choose_first_config(display, &[egl::RENDERABLE_TYPE, egl::OPENGL_BIT, egl::SURFACE_TYPE, egl::DONT_CARE, egl::NONE])?
+        .ok_or_else(|| HeadlessError::Egl("no configuration supports OpenGL".to_string()))?;
+    let context = egl.create_context(display, config, None, &[
+        egl::CONTEXT_MAJOR_VERSION, 3,
+        egl::CONTEXT_MINOR_VERSION, 3,
+        egl::CONTEXT_OPENGL_PROFILE_MASK, egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+        egl::NONE,
+    ])?;
+    egl.make_current(display, None, None, Some(context))?;
+
+    // Safety: the context was just made current, and is only used from this thread
+    Ok(unsafe { Context::new(EglBackend { egl, display, context }, false, Default::default()) }?)
+}
+
+/// Refuses images without pixels
+fn check_size((width, height): (u32, u32)) -> Result<(), HeadlessError> {
+    if width == 0 || height == 0 {
+        return Err(HeadlessError::Size(width, height));
+    }
+    Ok(())
+}
+
+/// Renders the scene as seen from the camera into an image of the given size, which can't be zero
+pub fn render_image<F: Facade>(display: &F, renderer: &Renderer, scene: &Scene, camera: &Camera, (width, height): (u32, u32)) -> Result<RgbaImage, HeadlessError> {
+    check_size((width, height))?;
+    // An sRGB color buffer, so that the image looks the same as the window
+    let color = SrgbTexture2d::empty_with_format(display, SrgbFormat::U8U8U8U8, MipmapsOption::NoMipmap, width, height)?;
+    let depth = DepthRenderBuffer::new(display, DepthFormat::I24, width, height)?;
+    let mut target = SimpleFrameBuffer::with_depth_buffer(display, &color, &depth)?;
+
+    renderer.draw_scene(display, &mut target, scene, camera, SceneObject::transform);
+
+    let raw: RawImage2d<u8> = color.read();
+    let image = RgbaImage::from_raw(raw.width, raw.height, raw.data.into_owned()).ok_or(HeadlessError::Readback)?;
+    // OpenGL rows start at the bottom of the image
+    Ok(imageops::flip_vertical(&image))
+}
+
+/// Loads a scene, moves it `time` seconds after it started, and renders it as seen from the camera
+pub fn render_scene<F: Facade>(display: &F, scene_path: &str, camera: &Camera, size: (u32, u32), time: f32) -> Result<RgbaImage, HeadlessError> {
+    // Before loading the scene, which takes a while
+    check_size(size)?;
+    let mut renderer = Renderer::new(display)?;
+    let mut scene = Scene::load(display, scene_path)?;
+
+    renderer.ambient = scene.ambient;
+    renderer.light_rotation = LIGHT_ROTATION_SPEED * time;
+    scene.update(time);
+    renderer.lights = scene.world_lights(SceneObject::transform);
+
+    if let Err(e) = renderer.prepare_shadows(display) {
+        eprintln!("Could not create shadow map: {e}");
+    }
+
+    render_image(display, &renderer, &scene, camera, size)
+}
+
+/// Renders a scene like `render_scene` into a PNG file, or any other format the extension of `path` names
+pub fn render_to_file(scene_path: &str, camera: &Camera, size: (u32, u32), time: f32, path: &str) -> Result<(), HeadlessError> {
+    let context = headless_context()?;
+    render_scene(&context, scene_path, camera, size, time)?.save(path)?;
+    Ok(())
+}
@@ -0,0 +1,31 @@
+//! OpenGL scene of a gas station, rendered with glium.
+//!
+//! The crate loads wavefront .obj models and RON scene files, places them with [`assets::transform::Transform`]
+//! and the matrix macros [`translate!`], [`rotate!`], [`scale!`] and [`identity!`], and draws them with lights,
+//! shadows and a skybox through [`model::renderer::Renderer`]. [`app::App`] runs a scene in a window, moving a
+//! [`camera::Camera`] around, while [`headless`] renders it offscreen.
+//!
+//! ```no_run
+//! use ogl_house_scene::camera::Camera;
+//! use ogl_house_scene::headless::{render_to_file, DEFAULT_SIZE};
+//!
+//! let camera = Camera { position: [0.5, 0.4, 1.5], yaw: 3.4, pitch: -0.15, ..Camera::default() };
+//! render_to_file("scenes/gas_station.ron", &camera, DEFAULT_SIZE, 0.0, "gas_station.png").unwrap();
+//! ```
+
+#![warn(missing_docs)]
+
+#[macro_use]
+extern crate glium;
+
+pub mod animation;
+pub mod app;
+pub mod assets;
+pub mod camera;
+pub mod clock;
+pub mod collision;
+pub mod event_handler;
+pub mod headless;
+pub mod math;
+pub mod model;
+pub mod scene;
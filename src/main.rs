@@ -1,35 +1,36 @@
-#[macro_use]
-extern crate glium;
-extern crate image;
+use std::env;
+use std::path::Path;
 
-use std::{cmp::max, include_bytes};
-use std::io::empty;
-
-use glium::{Display, Surface};
+use glium::Display;
 use glium::backend::glutin::DisplayCreationError;
 use glium::glutin;
-use glium::glutin::event_loop::{ControlFlow, EventLoop};
-use glium::texture::SrgbTexture2d;
+use glium::glutin::event_loop::EventLoop;
+
+use ogl_house_scene::app::{App, FrameMode, WINDOW_TITLE};
+use ogl_house_scene::camera::Camera;
+use ogl_house_scene::event_handler::actions::{Controls, DEFAULT_CONTROLS};
+use ogl_house_scene::headless::{render_to_file, DEFAULT_SIZE};
+use ogl_house_scene::model::renderer::Renderer;
+use ogl_house_scene::scene::{DEFAULT_SCENE, Scene};
 
-use model::generic_model::GenericModel;
+/// Arguments the program takes, printed when given others
+const USAGE: &str = "\
+Usage: ogl_house_scene [SCENE] [OPTIONS]
 
-use crate::assets::{
-    load_tex,
-    transform::Transform,
-    vertex::Light,
-};
-use crate::assets::vertex::{Normal, Vertex};
-use crate::event_handler::EventHandler;
-use crate::model::{get_light, get_light_rotation, Model, set_light, set_light_rotation, set_program};
+  SCENE                     scene file to load, scenes/gas_station.ron by default
 
-mod model;
-mod assets;
-mod event_handler;
+  --uncapped                render frames as fast as possible instead of syncing them to the display
+  --controls FILE           load the key bindings from FILE instead of controls.ron
+  --render-to FILE          render a single image to FILE without opening a window
+  --camera X,Y,Z,YAW,PITCH  place the camera of --render-to, angles in radians
+  --size WIDTHxHEIGHT       size of the image of --render-to, 800x600 by default
+  --time SECONDS            time of the animations in the image of --render-to";
 
 //Starts the window and the event loop
 fn start_opengl(
     title: &str,
     mut size: Option<(u32, u32)>,
+    frame_mode: FrameMode,
 ) -> (EventLoop<()>, Result<Display, DisplayCreationError>) {
     let size = size.get_or_insert((400, 400));
 
@@ -39,539 +40,118 @@ fn start_opengl(
         .with_title(title)
         .with_inner_size(glutin::dpi::LogicalSize::new(size.0, size.1));
 
-    let context = glutin::ContextBuilder::new().with_depth_buffer(24);
+    let context = glutin::ContextBuilder::new()
+        .with_depth_buffer(24)
+        .with_vsync(frame_mode == FrameMode::Vsync);
 
     let display = glium::Display::new(window, context, &event_loop);
     (event_loop, display)
 }
 
 fn main() {
-    let (event_loop, display) = match start_opengl("First", None) {
+    let mut scene_path = DEFAULT_SCENE.to_string();
+    let mut controls_path = None;
+    let mut frame_mode = FrameMode::Vsync;
+    let mut render_path = None;
+    let mut camera = Camera::default();
+    let mut size = DEFAULT_SIZE;
+    let mut time = 0.0;
+    let mut scene_given = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--uncapped" => frame_mode = FrameMode::Uncapped,
+            "--controls" => match args.next() {
+                Some(path) => controls_path = Some(path),
+                None => usage_error("Missing path after --controls"),
+            },
+            "--render-to" => match args.next() {
+                Some(path) => render_path = Some(path),
+                None => usage_error("Missing path after --render-to"),
+            },
+            "--camera" => match args.next().as_deref().and_then(parse_numbers) {
+                Some([x, y, z, yaw, pitch]) => camera = Camera { position: [x, y, z], yaw, pitch, ..camera },
+                _ => usage_error("Expected x,y,z,yaw,pitch after --camera"),
+            },
+            "--size" => match args.next().as_deref().and_then(parse_size) {
+                Some(parsed) => size = parsed,
+                None => usage_error("Expected WIDTHxHEIGHT after --size, both above zero"),
+            },
+            "--time" => match args.next().map(|arg| arg.parse()) {
+                Some(Ok(seconds)) => time = seconds,
+                _ => usage_error("Expected a number of seconds after --time"),
+            },
+            flag if flag.starts_with("--") => usage_error(&format!("Unknown option {flag}")),
+            _ if scene_given => usage_error(&format!("Unexpected argument {arg}, the scene is {scene_path}")),
+            _ => {
+                scene_path = arg;
+                scene_given = true;
+            }
+        }
+    }
+
+    // Renders a single image without opening a window
+    if let Some(path) = render_path {
+        match render_to_file(&scene_path, &camera, size, time, &path) {
+            Ok(()) => println!("Rendered {scene_path} to {path}"),
+            Err(e) => {
+                eprintln!("Could not render {scene_path} to {path}: {e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // The default controls file is optional, one given on the command line is not
+    let controls = match controls_path {
+        Some(path) => Some(path),
+        None => Path::new(DEFAULT_CONTROLS).exists().then(|| DEFAULT_CONTROLS.to_string()),
+    };
+    let controls = match controls {
+        Some(path) => Controls::load(&path).unwrap_or_else(|e| {
+            eprintln!("Could not load controls {path}, using the default ones: {e}");
+            Controls::default()
+        }),
+        None => Controls::default(),
+    };
+
+    let (event_loop, display) = match start_opengl(WINDOW_TITLE, None, frame_mode) {
         (event_loop, Ok(display)) => (event_loop, display),
         (_, Err(e)) => panic!("Could not create window: {e}"),
     };
 
-    // Instantiates a program source for all models
-    set_program(&display);
-
-    // Defining the draw parameters
-    let draw_params = glium::draw_parameters::DrawParameters {
-        depth: glium::Depth {
-            test: glium::DepthTest::IfLess,
-            write: true,
-            range: (0.0, 1.0),
-            ..Default::default()
-        },
-        backface_culling: glium::draw_parameters::BackfaceCullingMode::CullingDisabled,
-        ..Default::default()
+    // Compiles the programs shared by all models
+    let renderer = match Renderer::new(&display) {
+        Ok(renderer) => renderer,
+        Err(e) => panic!("Could not compile shaders: {e}"),
     };
 
-    /// Instantiates each model
-    let bus = GenericModel::from_obj(&display, "models/bus.obj".to_string());
-    let dragon = GenericModel::from_obj(&display, "models/Dragon.obj".to_string());
-    let gas_station = GenericModel::from_obj(&display, "models/Station.obj".to_string());
-    let dennis = GenericModel::from_obj(&display, "models/rp_dennis_posed_004_30k.OBJ".to_string());
-    let fabienne_percy = GenericModel::from_obj(&display, "models/rp_fabienne_percy_posed_001_60k.obj".to_string());
-    let altair = GenericModel::from_obj(&display, "models/assassins-creed-altair.obj".to_string());
-    let railgun1 = GenericModel::from_obj(&display, "models/Railgun_Prototype-Wavefront OBJ.obj".to_string());
-    let railgun2 = GenericModel::from_obj(&display, "models/Railgun_Prototype-Wavefront OBJ.obj".to_string());
-
-    let ground1_vertices:Vec<Vertex> = vec![
-        [-0.38, 0.0, -50.0],
-        [-0.38, 0.0, 50.0],
-        [-50.0, 0.0, 50.0],
-        [-50.0, 0.0, -50.0],
-    ]
-        .iter()
-        .map(|v| {
-            Vertex {
-                position: [v[0], v[1], v[2]],
-                tex_coords: [
-                    if v[2] == -50.0 { 0.0 } else { 100.0 },
-                    if v[0] == -50.0 { 0.0 } else { 100.0 },
-                ]
-            }
-        })
-        .collect();
-
-    let ground2_vertices:Vec<Vertex> = vec![
-        [0.98, 0.0, -50.0],
-        [0.98, 0.0, 50.0],
-        [50.0, 0.0, 50.0],
-        [50.0, 0.0, -50.0],
-    ]
-        .iter()
-        .map(|v| {
-            Vertex {
-                position: [v[0], v[1], v[2]],
-                tex_coords: [
-                    if v[2] == -50.0 { 0.0 } else { 100.0 },
-                    if v[0] == 0.98 { 0.0 } else { 100.0 },
-                ]
-            }
-        })
-        .collect();
-
-    let road_vertices:Vec<Vertex> = vec![
-        [-0.38, 0.0, -50.0],
-        [-0.38, 0.0, 50.0],
-        [0.98, 0.0, 50.0],
-        [0.98, 0.0, -50.0],
-    ]
-        .iter()
-        .map(|v| {
-            Vertex {
-                position: [v[0], v[1], v[2]],
-                tex_coords: [
-                    if v[2] == -50.0 { 0.0 } else { 10.0 },
-                    if v[0] == -0.38 { 0.0 } else { 1.0 },
-                ]
-            }
-        })
-        .collect();
-
-    let skybox_left_vertices: Vec<Vertex> = vec![
-        [-50.0, -50.0, -50.0],
-        [-50.0, -50.0, 50.0],
-        [-50.0, 50.0, 50.0],
-        [-50.0, 50.0, -50.0],
-    ]
-        .iter()
-        .map(|v| {
-            Vertex {
-                position: [v[0], v[1], v[2]],
-                tex_coords: [
-                    if v[2] == -50.0 { 0.001 } else { 0.2499 },
-                    if v[1] == -50.0 { 0.32999 } else { 0.65999 },
-                ]
-            }
-        })
-        .collect();
-
-    // Vertices of the front face of a cube of size 20
-    let skybox_front_vertices: Vec<Vertex> = vec![
-        [-50.0, -50.0, 50.0],
-        [50.0, -50.0, 50.0],
-        [50.0, 50.0, 50.0],
-        [-50.0, 50.0, 50.0],
-    ]
-        .iter()
-        .map(|v| {
-            Vertex {
-                position: [v[0], v[1], v[2]],
-                tex_coords: [
-                    if v[0] == -50.0 { 0.24999 } else { 0.49999 },
-                    if v[1] == -50.0 { 0.32999 } else { 0.65999 },
-                ],
-            }
-        })
-        .collect();
-
-    let skybox_top_vertices: Vec<Vertex> = vec![
-        [-50.0, 50.0, 50.0],
-        [50.0, 50.0, 50.0],
-        [50.0, 50.0, -50.0],
-        [-50.0, 50.0, -50.0],
-    ]
-        .iter()
-        .map(|v| {
-            Vertex {
-                position: [v[0], v[1], v[2]],
-                tex_coords: [
-                    if v[0] == -50.0 { 0.2499 } else { 0.499 },
-                    if v[2] == 50.0 { 0.6599 } else { 0.999 },
-                ],
-            }
-        })
-        .collect();
-
-    let skybox_right_vertices: Vec<Vertex> = vec![
-        [50.0, -50.0, -50.0],
-        [50.0, 50.0, -50.0],
-        [50.0, 50.0, 50.0],
-        [50.0, -50.0, 50.0],
-    ]
-        .iter()
-        .map(|v| {
-            Vertex {
-                position: [v[0], v[1], v[2]],
-                tex_coords: [
-                    if v[2] == 50.0 { 0.4999 } else { 0.7499 },
-                    if v[1] == -50.0 { 0.32999 } else { 0.65999 },
-                ],
-            }
-        })
-        .collect();
-
-    let skybox_back_vertices: Vec<Vertex> = vec![
-        [-50.0, -50.0, -50.0],
-        [50.0, -50.0, -50.0],
-        [50.0, 50.0, -50.0],
-        [-50.0, 50.0, -50.0],
-    ]
-        .iter()
-        .map(|v| {
-            Vertex {
-                position: [v[0], v[1], v[2]],
-                tex_coords: [
-                    if v[0] == 50.0 { 0.74999 } else { 1.0 },
-                    if v[1] == -50.0 { 0.32999 } else { 0.65999 },
-                ],
-            }
-        })
-        .collect();
-
-
-    let ground_normals: Vec<Normal> = vec![[0.0, 1.0, 0.0]; 4]
-        .iter()
-        .map(|n| {
-            Normal {
-                normal: [n[0], n[1], n[2]]
-            }
-        })
-        .collect();
-
-    let sky_normals: Vec<Normal> = (0..4)
-        .map(|n| {
-            Normal {
-                normal: [0.0; 3]
-            }
-        })
-        .collect();
-
-    let ground1 = GenericModel::new(&display, &ground1_vertices, &vec![0, 1, 2, 2, 3, 0], &ground_normals);
-    let ground2 = GenericModel::new(&display, &ground2_vertices, &vec![0, 1, 2, 2, 3, 0], &ground_normals);
-    let road = GenericModel::new(&display, &road_vertices, &vec![0, 1, 2, 2, 3, 0], &ground_normals);
-    let skybox_left = GenericModel::new(&display, &skybox_left_vertices, &vec![0, 1, 2, 2, 3, 0], &sky_normals);
-    let skybox_front = GenericModel::new(&display, &skybox_front_vertices, &vec![0, 1, 2, 2, 3, 0], &sky_normals);
-    let skybox_top = GenericModel::new(&display, &skybox_top_vertices, &vec![0, 1, 2, 2, 3, 0], &sky_normals);
-    let skybox_right = GenericModel::new(&display, &skybox_right_vertices, &vec![0, 1, 2, 2, 3, 0], &sky_normals);
-    let skybox_back = GenericModel::new(&display, &skybox_back_vertices, &vec![0, 1, 2, 2, 3, 0], &sky_normals);
-
-
-    let mut event_handler = EventHandler{
-        grow: 0.1,
-        ..Default::default()
+    // Instantiates each model of the scene
+    let scene = match Scene::load(&display, &scene_path) {
+        Ok(scene) => scene,
+        Err(e) => panic!("Could not load scene {scene_path}: {e}"),
     };
 
-    let bus_pos = (0.1, 0.01, 0.1);
-    let dragon_pos = (0.6, 0.55, -0.1);
-    let gas_station_pos = (0.1, 0.0, 0.0);
-    let dennis_pos = (-0.22, 0.0, 0.3);
-    let fabienne_pos = (-0.12, 0.0, 0.3);
-    let altair_pos = (-0.71, 0.0, -1.01);
-    let railgun1_pos = (-0.21, 0.07, -1.02);
-    let railgun2_pos = (-0.41, 0.07, -1.02);
-
-    let fabienne_tex = load_tex!(&display, "../textures/rp_fabienne_percy_posed_001_dif_2k.jpg", jpg);
-    let dennis_tex = load_tex!(&display, "../textures/rp_dennis_posed_004_dif_2k.jpg", jpg);
-    let altair_tex = load_tex!(&display, "../textures/kaleidoscope.jpg", jpg);
-    let ground1_tex = load_tex!(&display, "../textures/grass.jpg", jpg);
-    let ground2_tex = load_tex!(&display, "../textures/tough_grass.jpg", jpg);
-    let dragon_tex = load_tex!(&display, "../textures/Dragon_ground_color.jpg", jpg);
-    let station_tex = load_tex!(&display, "../textures/gasstation red.png", png);
-    let bus_tex = load_tex!(&display, "../textures/bus_d.png", png);
-    let railgun_tex = load_tex!(&display, "../textures/Railgun_color.jpg", jpg);
-    let road_tex = load_tex!(&display, "../textures/road.jpg", jpg);
-    let sky_tex = load_tex!(&display, "../textures/dawn.jpg", jpg);
-
-    let mut dragon_spin_self = 0.0f32;
-    let mut dragon_spin_around = 0.0f32;
-    let mut bus_translate_z = 0.0f32;
-    let mut dennis_translate_x = 0.0f32;
-    let mut fabienne_translate_x = 0.0f32;
-    let mut railgun_spin_self = 0.0f32;
-    let mut altair_spin_self = 0.0f32;
-
-    event_loop.run(move |event, _, control_flow| {
-        let mut target = display.draw();
-        target.clear_color_and_depth((0., 0., 1., 1.), 1.);
-
-        set_wait(control_flow, 16_666_667);
-
-        event_handler.handle_event(event, control_flow);
-        dragon_spin_self += 0.001;
-        dragon_spin_around += 0.01;
-        bus_translate_z += if bus_pos.2 + bus_translate_z < 2.2 {0.0001} else {0.0};
-        fabienne_translate_x -= if fabienne_translate_x + fabienne_pos.0 > -1.9 {0.00005} else {0.0};
-        dennis_translate_x -= if dennis_translate_x + dennis_pos.0 > -2.2 {0.000075} else {0.0};
-        railgun_spin_self += 0.01;
-        altair_spin_self -= 0.01;
-
-        let EventHandler {
-            grow,
-            tilt,
-            spin,
-            translate_x,
-            translate_y,
-            direction,
-            position,
-            up,
-            zfar,
-            znear,
-            fov,
-        } = event_handler;
-
-        let dimensions = target.get_dimensions();
-
-        // updates the light rotation matrix
-        set_light_rotation(get_light_rotation() + 0.02);
-
-        bus.draw(
-            &mut target,
-            &draw_params,
-            &Transform{
-                rotate_self: [spin, tilt, 0.],
-                scale: 5.0,
-                translation: [bus_pos.0, bus_pos.1, bus_pos.2 + bus_translate_z],
-                view: [position, direction, up],
-                frame_dimensions: Some(dimensions),
-                texture: Some(&bus_tex),
-                zfar,
-                znear,
-                fov,
-                ..Default::default()
-            }
-        );
-
-        dragon.draw(
-            &mut target,
-            &draw_params,
-            &Transform{
-                rotation: [0., dragon_spin_around, 0.],
-                rotate_self: [spin, dragon_spin_self, 0.],
-                scale: 2.4,
-                translation: [dragon_pos.0, dragon_pos.1, dragon_pos.2],
-                view: [position, direction, up],
-                frame_dimensions: Some(dimensions),
-                texture: Some(&dragon_tex),
-                zfar,
-                znear,
-                fov,
-                ..Default::default()
-            }
-        );
-
-        gas_station.draw(
-            &mut target,
-            &draw_params,
-            &Transform{
-                rotate_self: [spin, tilt, 0.],
-                scale: 17.5,
-                translation: [translate_x + gas_station_pos.0, translate_y + gas_station_pos.1, 0. + gas_station_pos.2],
-                view: [position, direction, up],
-                frame_dimensions: Some(dimensions),
-                texture: Some(&station_tex),
-                zfar,
-                znear,
-                fov,
-                ..Default::default()
-            }
-        );
-
-        dennis.draw(
-            &mut target,
-            &draw_params,
-            &Transform{
-                rotate_self: [spin, 3.0, 0.],
-                scale: 0.13,
-                translation: [dennis_translate_x + dennis_pos.0, dennis_pos.1, dennis_pos.2],
-                view: [position, direction, up],
-                frame_dimensions: Some(dimensions),
-                texture: Some(&dennis_tex),
-                zfar,
-                znear,
-                fov,
-                ..Default::default()
-            }
-        );
-
-        fabienne_percy.draw(
-            &mut target,
-            &draw_params,
-            &Transform{
-                rotate_self: [spin, 3.0, 0.],
-                scale: 0.13,
-                translation: [fabienne_translate_x + fabienne_pos.0, fabienne_pos.1, fabienne_pos.2],
-                view: [position, direction, up],
-                frame_dimensions: Some(dimensions),
-                texture: Some(&fabienne_tex),
-                zfar,
-                znear,
-                fov,
-                ..Default::default()
-            }
-        );
-
-        altair.draw(
-            &mut target,
-            &draw_params,
-            &Transform{
-                rotate_self: [spin, altair_spin_self, 0.],
-                scale: 0.30,
-                translation: [altair_pos.0, altair_pos.1, altair_pos.2],
-                view: [position, direction, up],
-                frame_dimensions: Some(dimensions),
-                texture: Some(&altair_tex),
-                zfar,
-                znear,
-                fov,
-                ..Default::default()
-            }
-        );
-
-        railgun1.draw(
-            &mut target,
-            &draw_params,
-            &Transform{
-                rotate_self: [spin, railgun_spin_self, 0.],
-                scale: 30.17,
-                translation: [railgun1_pos.0, railgun1_pos.1, railgun1_pos.2],
-                view: [position, direction, up],
-                frame_dimensions: Some(dimensions),
-                texture: Some(&railgun_tex),
-                zfar,
-                znear,
-                fov,
-                ..Default::default()
-            }
-        );
-
-        railgun2.draw(
-            &mut target,
-            &draw_params,
-            &Transform{
-                rotate_self: [spin, railgun_spin_self, 0.],
-                scale: 30.17,
-                translation: [railgun2_pos.0, railgun2_pos.1, railgun2_pos.2],
-                view: [position, direction, up],
-                frame_dimensions: Some(dimensions),
-                texture: Some(&railgun_tex),
-                zfar,
-                znear,
-                fov,
-                ..Default::default()
-            }
-        );
-
-        ground1.draw(
-            &mut target,
-            &draw_params,
-            &Transform {
-                texture: Some(&ground1_tex),
-                frame_dimensions: Some(dimensions),
-                view: [position, direction, up],
-                zfar,
-                znear,
-                fov,
-                ..Default::default()
-            }
-        );
-
-        ground2.draw(
-            &mut target,
-            &draw_params,
-            &Transform {
-                texture: Some(&ground2_tex),
-                frame_dimensions: Some(dimensions),
-                view: [position, direction, up],
-                zfar,
-                znear,
-                fov,
-                ..Default::default()
-            }
-        );
-
-        road.draw(
-            &mut target,
-            &draw_params,
-            &Transform {
-                texture: Some(&road_tex),
-                frame_dimensions: Some(dimensions),
-                view: [position, direction, up],
-                zfar,
-                znear,
-                fov,
-                ..Default::default()
-            }
-        );
-
-        skybox_left.draw(
-            &mut target,
-            &draw_params,
-            &Transform {
-                texture: Some(&sky_tex),
-                frame_dimensions: Some(dimensions),
-                view: [position, direction, up],
-                zfar,
-                znear,
-                fov,
-                ..Default::default()
-            }
-        );
-
-        skybox_right.draw(
-            &mut target,
-            &draw_params,
-            &Transform {
-                texture: Some(&sky_tex),
-                frame_dimensions: Some(dimensions),
-                view: [position, direction, up],
-                zfar,
-                znear,
-                fov,
-                ..Default::default()
-            }
-        );
-
-        skybox_top.draw(
-            &mut target,
-            &draw_params,
-            &Transform {
-                texture: Some(&sky_tex),
-                frame_dimensions: Some(dimensions),
-                view: [position, direction, up],
-                zfar,
-                znear,
-                fov,
-                ..Default::default()
-            }
-        );
-
-        skybox_back.draw(
-            &mut target,
-            &draw_params,
-            &Transform {
-                texture: Some(&sky_tex),
-                frame_dimensions: Some(dimensions),
-                view: [position, direction, up],
-                zfar,
-                znear,
-                fov,
-                ..Default::default()
-            }
-        );
+    App::new(display, renderer, scene, controls).run(event_loop)
+}
 
-        skybox_front.draw(
-            &mut target,
-            &draw_params,
-            &Transform {
-                texture: Some(&sky_tex),
-                frame_dimensions: Some(dimensions),
-                view: [position, direction, up],
-                zfar,
-                znear,
-                fov,
-                ..Default::default()
-            }
-        );
+/// Prints what went wrong and how to run the program, then exits
+fn usage_error(message: &str) -> ! {
+    eprintln!("{message}");
+    eprintln!("{USAGE}");
+    std::process::exit(2);
+}
 
-        target.finish().unwrap();
-    });
+/// Parses a size given as `WIDTHxHEIGHT`, neither of which can be zero
+fn parse_size(size: &str) -> Option<(u32, u32)> {
+    let (width, height) = size.split_once('x')?;
+    let size = (width.parse().ok()?, height.parse().ok()?);
+    (size.0 > 0 && size.1 > 0).then_some(size)
 }
 
-/// Defines the wait time for the next frame
-fn set_wait(cf: &mut ControlFlow, nanos: u64) {
-    let next_frame_time = std::time::Instant::now() + std::time::Duration::from_nanos(nanos);
-    *cf = glutin::event_loop::ControlFlow::WaitUntil(next_frame_time);
-}
\ No newline at end of file
+/// Parses a comma separated list of exactly `N` numbers
+fn parse_numbers<const N: usize>(list: &str) -> Option<[f32; N]> {
+    let numbers: Vec<f32> = list.split(',').map(|number| number.trim().parse().ok()).collect::<Option<_>>()?;
+    numbers.try_into().ok()
+}
@@ -0,0 +1,486 @@
+//! Column-major 3x3 and 4x4 matrices, laid out like glium uploads them
+
+use std::ops::Mul;
+
+use glium::uniforms::{AsUniformValue, UniformValue};
+
+use crate::math::vector::{Vec3, Vec4};
+
+/// 3x3 matrix, rotating and scaling vectors
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mat3 {
+    /// Columns of the matrix, the images of the x, y and z axes
+    pub cols: [Vec3; 3],
+}
+
+impl Default for Mat3 {
+    fn default() -> Self {
+        Mat3::IDENTITY
+    }
+}
+
+impl Mat3 {
+    /// Matrix leaving every vector as it is
+    pub const IDENTITY: Mat3 = Mat3::from_cols(Vec3::X, Vec3::Y, Vec3::Z);
+
+    /// Creates a matrix from its columns
+    pub const fn from_cols(x: Vec3, y: Vec3, z: Vec3) -> Mat3 {
+        Mat3 { cols: [x, y, z] }
+    }
+
+    /// Row `index` of the matrix
+    pub fn row(&self, index: usize) -> Vec3 {
+        Vec3::new(self.cols[0][index], self.cols[1][index], self.cols[2][index])
+    }
+
+    /// Matrix with the rows and columns swapped
+    pub fn transpose(&self) -> Mat3 {
+        Mat3::from_cols(self.row(0), self.row(1), self.row(2))
+    }
+
+    /// Determinant, zero when the matrix flattens space
+    pub fn determinant(&self) -> f32 {
+        let [x, y, z] = self.cols;
+        x.dot(y.cross(z))
+    }
+
+    /// Matrix undoing this one, `None` when it flattens space and can't be undone
+    pub fn inverse(&self) -> Option<Mat3> {
+        let [x, y, z] = self.cols;
+        let determinant = self.determinant();
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+        // The rows of the inverse are perpendicular to two of the columns each
+        let rows = Mat3::from_cols(y.cross(z), z.cross(x), x.cross(y));
+        Some(rows.transpose() * (1.0 / determinant))
+    }
+}
+
+impl Mul for Mat3 {
+    type Output = Mat3;
+
+    /// Matrix applying `other` first and then `self`
+    fn mul(self, other: Mat3) -> Mat3 {
+        Mat3 { cols: other.cols.map(|col| self * col) }
+    }
+}
+
+impl Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        self.cols[0] * v.x + self.cols[1] * v.y + self.cols[2] * v.z
+    }
+}
+
+impl Mul<f32> for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, scalar: f32) -> Mat3 {
+        Mat3 { cols: self.cols.map(|col| col * scalar) }
+    }
+}
+
+impl From<[[f32; 3]; 3]> for Mat3 {
+    fn from(cols: [[f32; 3]; 3]) -> Self {
+        Mat3 { cols: cols.map(Vec3::from) }
+    }
+}
+
+impl From<Mat3> for [[f32; 3]; 3] {
+    fn from(matrix: Mat3) -> Self {
+        matrix.cols.map(Vec3::to_array)
+    }
+}
+
+/// Upper left 3x3 part of the matrix, its rotation and scale without the translation
+impl From<Mat4> for Mat3 {
+    fn from(matrix: Mat4) -> Self {
+        Mat3 { cols: [0, 1, 2].map(|i| matrix.cols[i].truncate()) }
+    }
+}
+
+impl AsUniformValue for Mat3 {
+    fn as_uniform_value(&self) -> UniformValue<'_> {
+        UniformValue::Mat3((*self).into())
+    }
+}
+
+/// 4x4 matrix, transforming points and vectors in homogeneous coordinates
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mat4 {
+    /// Columns of the matrix, the images of the x, y and z axes and of the origin
+    pub cols: [Vec4; 4],
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::IDENTITY
+    }
+}
+
+impl Mat4 {
+    /// Matrix leaving every point as it is
+    pub const IDENTITY: Mat4 = Mat4::from_cols(
+        Vec4::new(1.0, 0.0, 0.0, 0.0),
+        Vec4::new(0.0, 1.0, 0.0, 0.0),
+        Vec4::new(0.0, 0.0, 1.0, 0.0),
+        Vec4::new(0.0, 0.0, 0.0, 1.0),
+    );
+
+    /// Creates a matrix from its columns
+    pub const fn from_cols(x: Vec4, y: Vec4, z: Vec4, w: Vec4) -> Mat4 {
+        Mat4 { cols: [x, y, z, w] }
+    }
+
+    /// Matrix moving points by `offset`
+    pub fn from_translation(offset: Vec3) -> Mat4 {
+        Mat4 { cols: [Mat4::IDENTITY.cols[0], Mat4::IDENTITY.cols[1], Mat4::IDENTITY.cols[2], offset.extend(1.0)] }
+    }
+
+    /// Matrix scaling each axis by the matching component of `factors`
+    pub fn from_scale(factors: Vec3) -> Mat4 {
+        Mat4::from_cols(
+            Vec4::new(factors.x, 0.0, 0.0, 0.0),
+            Vec4::new(0.0, factors.y, 0.0, 0.0),
+            Vec4::new(0.0, 0.0, factors.z, 0.0),
+            Vec4::new(0.0, 0.0, 0.0, 1.0),
+        )
+    }
+
+    /// Matrix applying the rotation and scale of `matrix`, without any translation
+    pub fn from_mat3(matrix: Mat3) -> Mat4 {
+        let [x, y, z] = matrix.cols;
+        Mat4::from_cols(x.extend(0.0), y.extend(0.0), z.extend(0.0), Vec4::new(0.0, 0.0, 0.0, 1.0))
+    }
+
+    /// Rotation by `angle` radians around the x axis, like `rotate![angle, x]`
+    pub fn from_rotation_x(angle: f32) -> Mat4 {
+        let (sin, cos) = angle.sin_cos();
+        Mat4::from_mat3(Mat3::from_cols(Vec3::X, Vec3::new(0.0, cos, sin), Vec3::new(0.0, -sin, cos)))
+    }
+
+    /// Rotation by `angle` radians around the y axis, like `rotate![angle, y]`
+    pub fn from_rotation_y(angle: f32) -> Mat4 {
+        let (sin, cos) = angle.sin_cos();
+        Mat4::from_mat3(Mat3::from_cols(Vec3::new(cos, 0.0, -sin), Vec3::Y, Vec3::new(sin, 0.0, cos)))
+    }
+
+    /// Rotation by `angle` radians around the z axis, like `rotate![angle, z]`
+    pub fn from_rotation_z(angle: f32) -> Mat4 {
+        let (sin, cos) = angle.sin_cos();
+        Mat4::from_mat3(Mat3::from_cols(Vec3::new(cos, sin, 0.0), Vec3::new(-sin, cos, 0.0), Vec3::Z))
+    }
+
+    /// View matrix of a camera at `position` looking along `direction`, with x to the right, y up and z forward.
+    ///
+    /// A zero `direction` looks along the z axis, and an `up` parallel to the direction is replaced by any
+    /// perpendicular one, instead of filling the matrix with NaNs.
+    pub fn look_to(position: Vec3, direction: Vec3, up: Vec3) -> Mat4 {
+        let forward = direction.normalize_or(Vec3::Z);
+        let right = up.cross(forward).try_normalize().unwrap_or_else(|| forward.any_perpendicular());
+        let up = forward.cross(right);
+
+        Mat4::from_cols(
+            Vec4::new(right.x, up.x, forward.x, 0.0),
+            Vec4::new(right.y, up.y, forward.y, 0.0),
+            Vec4::new(right.z, up.z, forward.z, 0.0),
+            Vec4::new(-position.dot(right), -position.dot(up), -position.dot(forward), 1.0),
+        )
+    }
+
+    /// Perspective projection looking down the positive z axis, `fov` being the vertical field of view in radians
+    /// and `aspect_ratio` the width of the frame over its height
+    pub fn perspective(aspect_ratio: f32, fov: f32, znear: f32, zfar: f32) -> Mat4 {
+        let f = 1.0 / (fov / 2.0).tan();
+        Mat4::from_cols(
+            Vec4::new(f / aspect_ratio, 0.0, 0.0, 0.0),
+            Vec4::new(0.0, f, 0.0, 0.0),
+            Vec4::new(0.0, 0.0, (zfar + znear) / (zfar - znear), 1.0),
+            Vec4::new(0.0, 0.0, -(2.0 * zfar * znear) / (zfar - znear), 0.0),
+        )
+    }
+
+    /// Orthographic projection looking down the positive z axis, of the box `2 * half_width` wide and
+    /// `2 * half_height` high between `znear` and `zfar`
+    pub fn orthographic(half_width: f32, half_height: f32, znear: f32, zfar: f32) -> Mat4 {
+        Mat4::from_cols(
+            Vec4::new(1.0 / half_width, 0.0, 0.0, 0.0),
+            Vec4::new(0.0, 1.0 / half_height, 0.0, 0.0),
+            Vec4::new(0.0, 0.0, 2.0 / (zfar - znear), 0.0),
+            Vec4::new(0.0, 0.0, -(zfar + znear) / (zfar - znear), 1.0),
+        )
+    }
+
+    /// Row `index` of the matrix
+    pub fn row(&self, index: usize) -> Vec4 {
+        Vec4::new(self.cols[0][index], self.cols[1][index], self.cols[2][index], self.cols[3][index])
+    }
+
+    /// Matrix with the rows and columns swapped
+    pub fn transpose(&self) -> Mat4 {
+        Mat4::from_cols(self.row(0), self.row(1), self.row(2), self.row(3))
+    }
+
+    /// Products of the 2x2 minors of the first two and of the last two columns, from which both the determinant
+    /// and the inverse are computed
+    fn minors(&self) -> ([f32; 6], [f32; 6]) {
+        let [[a00, a01, a02, a03], [a10, a11, a12, a13], [a20, a21, a22, a23], [a30, a31, a32, a33]] = self.cols.map(Vec4::to_array);
+        (
+            [
+                a00 * a11 - a01 * a10,
+                a00 * a12 - a02 * a10,
+                a00 * a13 - a03 * a10,
+                a01 * a12 - a02 * a11,
+                a01 * a13 - a03 * a11,
+                a02 * a13 - a03 * a12,
+            ],
+            [
+                a20 * a31 - a21 * a30,
+                a20 * a32 - a22 * a30,
+                a20 * a33 - a23 * a30,
+                a21 * a32 - a22 * a31,
+                a21 * a33 - a23 * a31,
+                a22 * a33 - a23 * a32,
+            ],
+        )
+    }
+
+    /// Determinant, zero when the matrix flattens space
+    pub fn determinant(&self) -> f32 {
+        let ([s0, s1, s2, s3, s4, s5], [c0, c1, c2, c3, c4, c5]) = self.minors();
+        s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0
+    }
+
+    /// Matrix undoing this one, `None` when it flattens space and can't be undone
+    pub fn inverse(&self) -> Option<Mat4> {
+        let [[a00, a01, a02, a03], [a10, a11, a12, a13], [a20, a21, a22, a23], [a30, a31, a32, a33]] = self.cols.map(Vec4::to_array);
+        let ([s0, s1, s2, s3, s4, s5], [c0, c1, c2, c3, c4, c5]) = self.minors();
+
+        let determinant = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+
+        // Adjugate over determinant, whichever of rows or columns the arrays are read as
+        let adjugate = Mat4::from_cols(
+            Vec4::new(a11 * c5 - a12 * c4 + a13 * c3, -a01 * c5 + a02 * c4 - a03 * c3, a31 * s5 - a32 * s4 + a33 * s3, -a21 * s5 + a22 * s4 - a23 * s3),
+            Vec4::new(-a10 * c5 + a12 * c2 - a13 * c1, a00 * c5 - a02 * c2 + a03 * c1, -a30 * s5 + a32 * s2 - a33 * s1, a20 * s5 - a22 * s2 + a23 * s1),
+            Vec4::new(a10 * c4 - a11 * c2 + a13 * c0, -a00 * c4 + a01 * c2 - a03 * c0, a30 * s4 - a31 * s2 + a33 * s0, -a20 * s4 + a21 * s2 - a23 * s0),
+            Vec4::new(-a10 * c3 + a11 * c1 - a12 * c0, a00 * c3 - a01 * c1 + a02 * c0, -a30 * s3 + a31 * s1 - a32 * s0, a20 * s3 - a21 * s1 + a22 * s0),
+        );
+        Some(adjugate * (1.0 / determinant))
+    }
+
+    /// Transforms a point, which the translation applies to
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        (*self * point.extend(1.0)).truncate()
+    }
+
+    /// Transforms a direction, which the translation doesn't apply to
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        (*self * vector.extend(0.0)).truncate()
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    /// Matrix applying `other` first and then `self`
+    fn mul(self, other: Mat4) -> Mat4 {
+        Mat4 { cols: other.cols.map(|col| self * col) }
+    }
+}
+
+impl Mul<Vec4> for Mat4 {
+    type Output = Vec4;
+
+    fn mul(self, v: Vec4) -> Vec4 {
+        self.cols[0] * v.x + self.cols[1] * v.y + self.cols[2] * v.z + self.cols[3] * v.w
+    }
+}
+
+impl Mul<f32> for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, scalar: f32) -> Mat4 {
+        Mat4 { cols: self.cols.map(|col| col * scalar) }
+    }
+}
+
+impl From<[[f32; 4]; 4]> for Mat4 {
+    fn from(cols: [[f32; 4]; 4]) -> Self {
+        Mat4 { cols: cols.map(Vec4::from) }
+    }
+}
+
+impl From<Mat4> for [[f32; 4]; 4] {
+    fn from(matrix: Mat4) -> Self {
+        matrix.cols.map(Vec4::to_array)
+    }
+}
+
+impl AsUniformValue for Mat4 {
+    fn as_uniform_value(&self) -> UniformValue<'_> {
+        UniformValue::Mat4((*self).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+    use crate::{identity, rotate, scale, translate};
+
+    fn assert_close(a: Mat4, b: Mat4) {
+        for (x, y) in <[[f32; 4]; 4]>::from(a).iter().flatten().zip(<[[f32; 4]; 4]>::from(b).iter().flatten()) {
+            assert!((x - y).abs() < 1e-5, "{a:?} != {b:?}");
+        }
+    }
+
+    fn assert_close3(a: Vec3, b: Vec3) {
+        assert!(a.distance(b) < 1e-5, "{a:?} != {b:?}");
+    }
+
+    /// Neither orthogonal nor symmetric, with a translation
+    fn sample() -> Mat4 {
+        Mat4::from([
+            [2.0, 0.5, 0.0, 0.0],
+            [1.0, 3.0, -1.0, 0.0],
+            [0.0, 0.25, 1.5, 0.0],
+            [4.0, -2.0, 7.0, 1.0],
+        ])
+    }
+
+    #[test]
+    fn multiplication_applies_the_right_matrix_first() {
+        let moved = Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0));
+        let scaled = Mat4::from_scale(Vec3::splat(2.0));
+        assert_close3((moved * scaled).transform_point(Vec3::ONE), Vec3::new(3.0, 4.0, 5.0));
+        assert_close3((scaled * moved).transform_point(Vec3::ONE), Vec3::new(4.0, 6.0, 8.0));
+        assert_eq!(sample() * Mat4::IDENTITY, sample());
+        assert_eq!(Mat4::IDENTITY * sample(), sample());
+    }
+
+    #[test]
+    fn points_and_vectors() {
+        let moved = Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(moved.transform_point(Vec3::ZERO), Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(moved.transform_vector(Vec3::X), Vec3::X);
+    }
+
+    #[test]
+    fn matches_the_macros() {
+        let angle = 0.7f32;
+        assert_close(Mat4::from_rotation_x(angle), rotate![angle, x].into());
+        assert_close(Mat4::from_rotation_y(angle), rotate![angle, y].into());
+        assert_close(Mat4::from_rotation_z(angle), rotate![angle, z].into());
+        assert_close(Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0)), translate![1.0, 2.0, 3.0].into());
+        assert_close(Mat4::from_scale(Vec3::splat(0.5)), scale![0.5].into());
+    }
+
+    #[test]
+    fn rotations_follow_the_right_hand_rule() {
+        assert_close3(Mat4::from_rotation_z(FRAC_PI_2).transform_vector(Vec3::X), Vec3::Y);
+        assert_close3(Mat4::from_rotation_x(FRAC_PI_2).transform_vector(Vec3::Y), Vec3::Z);
+        assert_close3(Mat4::from_rotation_y(FRAC_PI_2).transform_vector(Vec3::Z), Vec3::X);
+    }
+
+    #[test]
+    fn transpose() {
+        let m = sample();
+        assert_eq!(m.transpose().cols[3], Vec4::new(0.0, 0.0, 0.0, 1.0));
+        assert_eq!(m.transpose().row(3), m.cols[3]);
+        assert_eq!(m.transpose().transpose(), m);
+        assert_eq!(Mat3::from(m).transpose().row(1), Vec3::new(1.0, 3.0, -1.0));
+    }
+
+    #[test]
+    fn determinant() {
+        assert_eq!(Mat4::IDENTITY.determinant(), 1.0);
+        assert_eq!(Mat4::from_scale(Vec3::new(2.0, 3.0, 4.0)).determinant(), 24.0);
+        // Translations don't change volumes, so only the 3x3 part counts: 2 * (3 * 1.5 + 0.25) - 1 * (0.5 * 1.5)
+        assert!((sample().determinant() - 8.75).abs() < 1e-5);
+        assert!((Mat3::from(sample()).determinant() - 8.75).abs() < 1e-5);
+        assert_eq!(Mat4::from_rotation_y(1.0).transpose().determinant().round(), 1.0);
+    }
+
+    #[test]
+    fn inverse() {
+        let m = sample();
+        let inverse = m.inverse().unwrap();
+        assert_close(m * inverse, Mat4::IDENTITY);
+        assert_close(inverse * m, Mat4::IDENTITY);
+
+        let moved = Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0));
+        assert_close(moved.inverse().unwrap(), Mat4::from_translation(Vec3::new(-1.0, -2.0, -3.0)));
+
+        let rotation = Mat4::from_rotation_x(0.3) * Mat4::from_rotation_y(1.2);
+        assert_close(rotation.inverse().unwrap(), rotation.transpose());
+
+        let m3 = Mat3::from(m);
+        let product = m3 * m3.inverse().unwrap();
+        for (col, axis) in product.cols.iter().zip([Vec3::X, Vec3::Y, Vec3::Z]) {
+            assert_close3(*col, axis);
+        }
+    }
+
+    #[test]
+    fn singular_matrices_have_no_inverse() {
+        assert_eq!(Mat4::from_scale(Vec3::new(1.0, 0.0, 1.0)).inverse(), None);
+        assert_eq!(Mat3::from_cols(Vec3::X, Vec3::Y, Vec3::X * 2.0).inverse(), None);
+    }
+
+    #[test]
+    fn look_to() {
+        // At (1, 2, 3) looking down the negative x axis: the camera's right is the world's z
+        let view = Mat4::look_to(Vec3::new(1.0, 2.0, 3.0), Vec3::new(-2.0, 0.0, 0.0), Vec3::Y);
+        assert_close(view, Mat4::from([
+            [0.0, 0.0, -1.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [-3.0, -2.0, 1.0, 1.0],
+        ]));
+        assert_close3(view.transform_point(Vec3::new(1.0, 2.0, 3.0)), Vec3::ZERO);
+        assert_close3(view.transform_point(Vec3::new(0.0, 2.0, 3.0)), Vec3::Z);
+    }
+
+    #[test]
+    fn look_to_degenerate_directions() {
+        let straight_up = Mat4::look_to(Vec3::ZERO, Vec3::Y, Vec3::Y);
+        let nowhere = Mat4::look_to(Vec3::ZERO, Vec3::ZERO, Vec3::Y);
+        for m in [straight_up, nowhere] {
+            assert!(<[[f32; 4]; 4]>::from(m).iter().flatten().all(|x| x.is_finite()));
+            assert!((m.determinant() - 1.0).abs() < 1e-5);
+        }
+        assert_close3(straight_up.transform_vector(Vec3::Y), Vec3::Z);
+        assert_close(nowhere, Mat4::IDENTITY);
+    }
+
+    #[test]
+    fn projections() {
+        let perspective = Mat4::perspective(2.0, FRAC_PI_2, 1.0, 3.0);
+        assert_close(perspective, Mat4::from([
+            [0.5, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 2.0, 1.0],
+            [0.0, 0.0, -3.0, 0.0],
+        ]));
+        // The near and far planes end up at the bottom and top of the depth range
+        let near = perspective * Vec4::new(0.0, 0.0, 1.0, 1.0);
+        let far = perspective * Vec4::new(0.0, 0.0, 3.0, 1.0);
+        assert_eq!((near.z / near.w, far.z / far.w), (-1.0, 1.0));
+
+        let orthographic = Mat4::orthographic(2.0, 4.0, 1.0, 5.0);
+        assert_close3(orthographic.transform_point(Vec3::new(2.0, -4.0, 5.0)), Vec3::new(1.0, -1.0, 1.0));
+        assert_close3(orthographic.transform_point(Vec3::new(0.0, 0.0, 1.0)), Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn uniforms() {
+        assert!(matches!(Mat4::IDENTITY.as_uniform_value(), UniformValue::Mat4(m) if m == identity!()));
+        assert!(matches!(Mat3::IDENTITY.as_uniform_value(), UniformValue::Mat3([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]])));
+    }
+}
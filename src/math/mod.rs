@@ -0,0 +1,13 @@
+//! Vectors, matrices and quaternions.
+//!
+//! Matrices are column-major like the arrays glium uploads, and all types convert to and from the plain arrays
+//! used by the rest of the crate. Normalizing a zero vector or inverting a singular matrix doesn't produce NaNs:
+//! each operation either returns an `Option` or documents its fallback.
+
+mod matrix;
+mod quat;
+mod vector;
+
+pub use matrix::{Mat3, Mat4};
+pub use quat::{EulerOrder, Quat};
+pub use vector::{Vec2, Vec3, Vec4};
@@ -0,0 +1,426 @@
+//! Quaternions representing rotations
+
+use std::ops::{Mul, Neg};
+
+use glium::uniforms::{AsUniformValue, UniformValue};
+
+use crate::math::matrix::{Mat3, Mat4};
+use crate::math::vector::{Vec3, Vec4};
+
+/// Dot products over which two rotations are close enough for slerp to fall back to a normalized lerp
+const SLERP_THRESHOLD: f32 = 0.9995;
+
+/// Order in which the rotations around each axis of the world are applied by `Quat::from_euler`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EulerOrder {
+    /// Around x first, then y, then z
+    Xyz,
+    /// Around x first, then z, then y
+    Xzy,
+    /// Around y first, then x, then z
+    Yxz,
+    /// Around y first, then z, then x
+    Yzx,
+    /// Around z first, then x, then y
+    Zxy,
+    /// Around z first, then y, then x
+    Zyx,
+}
+
+impl EulerOrder {
+    /// Indices of the axes, in the order their rotations are applied
+    fn axes(self) -> [usize; 3] {
+        match self {
+            EulerOrder::Xyz => [0, 1, 2],
+            EulerOrder::Xzy => [0, 2, 1],
+            EulerOrder::Yxz => [1, 0, 2],
+            EulerOrder::Yzx => [1, 2, 0],
+            EulerOrder::Zxy => [2, 0, 1],
+            EulerOrder::Zyx => [2, 1, 0],
+        }
+    }
+}
+
+/// Rotation stored as a unit quaternion, `w` being the real part
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Quat {
+    /// First imaginary component
+    pub x: f32,
+    /// Second imaginary component
+    pub y: f32,
+    /// Third imaginary component
+    pub z: f32,
+    /// Real component
+    pub w: f32,
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Quat::IDENTITY
+    }
+}
+
+impl Quat {
+    /// Rotation leaving every vector as it is
+    pub const IDENTITY: Quat = Quat::from_xyzw(0.0, 0.0, 0.0, 1.0);
+
+    /// Creates a quaternion from its components, which should have a length of 1 to be a rotation
+    pub const fn from_xyzw(x: f32, y: f32, z: f32, w: f32) -> Quat {
+        Quat { x, y, z, w }
+    }
+
+    /// Rotation by `angle` radians around `axis`, following the right-hand rule.
+    ///
+    /// `axis` doesn't need to be of length 1, the identity is returned when it is zero.
+    pub fn from_axis_angle(axis: Vec3, angle: f32) -> Quat {
+        match axis.try_normalize() {
+            Some(axis) => {
+                let (sin, cos) = (angle / 2.0).sin_cos();
+                let v = axis * sin;
+                Quat::from_xyzw(v.x, v.y, v.z, cos)
+            }
+            None => Quat::IDENTITY,
+        }
+    }
+
+    /// Rotation by `angles.x` radians around the x axis, `angles.y` around y and `angles.z` around z, applied in
+    /// the given order around the fixed axes of the world
+    pub fn from_euler(order: EulerOrder, angles: Vec3) -> Quat {
+        let axes = [Vec3::X, Vec3::Y, Vec3::Z];
+        order.axes().iter().fold(Quat::IDENTITY, |rotation, &axis| {
+            Quat::from_axis_angle(axes[axis], angles[axis]) * rotation
+        })
+    }
+
+    /// Rotation of the orthonormal basis given as the columns of `matrix`, which shouldn't scale or mirror
+    pub fn from_mat3(matrix: Mat3) -> Quat {
+        // Element at `row` and `column`
+        let m = |row: usize, column: usize| matrix.cols[column][row];
+        let trace = m(0, 0) + m(1, 1) + m(2, 2);
+
+        // Divides by the largest of the components, to keep precision
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quat::from_xyzw((m(2, 1) - m(1, 2)) / s, (m(0, 2) - m(2, 0)) / s, (m(1, 0) - m(0, 1)) / s, s / 4.0)
+        } else if m(0, 0) > m(1, 1) && m(0, 0) > m(2, 2) {
+            let s = (1.0 + m(0, 0) - m(1, 1) - m(2, 2)).sqrt() * 2.0;
+            Quat::from_xyzw(s / 4.0, (m(0, 1) + m(1, 0)) / s, (m(0, 2) + m(2, 0)) / s, (m(2, 1) - m(1, 2)) / s)
+        } else if m(1, 1) > m(2, 2) {
+            let s = (1.0 + m(1, 1) - m(0, 0) - m(2, 2)).sqrt() * 2.0;
+            Quat::from_xyzw((m(0, 1) + m(1, 0)) / s, s / 4.0, (m(1, 2) + m(2, 1)) / s, (m(0, 2) - m(2, 0)) / s)
+        } else {
+            let s = (1.0 + m(2, 2) - m(0, 0) - m(1, 1)).sqrt() * 2.0;
+            Quat::from_xyzw((m(0, 2) + m(2, 0)) / s, (m(1, 2) + m(2, 1)) / s, s / 4.0, (m(1, 0) - m(0, 1)) / s)
+        };
+        q.normalize()
+    }
+
+    /// Rotation turning the z axis towards `direction` and the y axis as close to `up` as possible, the
+    /// orientation of an object or a camera looking along `direction`.
+    ///
+    /// A zero `direction` looks along the z axis, and an `up` parallel to it is replaced by any perpendicular one.
+    pub fn look_to(direction: Vec3, up: Vec3) -> Quat {
+        let forward = direction.normalize_or(Vec3::Z);
+        let right = up.cross(forward).try_normalize().unwrap_or_else(|| forward.any_perpendicular());
+        Quat::from_mat3(Mat3::from_cols(right, forward.cross(right), forward))
+    }
+
+    /// Rotation of something at `position` looking at `target`, see `look_to`
+    pub fn look_at(position: Vec3, target: Vec3, up: Vec3) -> Quat {
+        Quat::look_to(target - position, up)
+    }
+
+    /// Imaginary part, the axis scaled by the sine of half the angle
+    pub fn vector(self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    /// Components as a vector, in x, y, z, w order
+    pub fn to_vec4(self) -> Vec4 {
+        Vec4::new(self.x, self.y, self.z, self.w)
+    }
+
+    /// Sum of the products of the matching components, the cosine of half the angle between two rotations
+    pub fn dot(self, other: Quat) -> f32 {
+        self.to_vec4().dot(other.to_vec4())
+    }
+
+    /// Length of the quaternion, 1 for rotations
+    pub fn length(self) -> f32 {
+        self.to_vec4().length()
+    }
+
+    /// Quaternion of length 1, the identity when it is too short to have a direction
+    pub fn normalize(self) -> Quat {
+        self.to_vec4().try_normalize().map_or(Quat::IDENTITY, Quat::from)
+    }
+
+    /// Quaternion with the imaginary part negated, which for a rotation is the opposite rotation
+    pub fn conjugate(self) -> Quat {
+        Quat::from_xyzw(-self.x, -self.y, -self.z, self.w)
+    }
+
+    /// Opposite rotation, the identity for a zero quaternion
+    pub fn inverse(self) -> Quat {
+        let length_squared = self.dot(self);
+        if length_squared == 0.0 || !length_squared.is_finite() {
+            return Quat::IDENTITY;
+        }
+        let Quat { x, y, z, w } = self.conjugate();
+        Quat::from_xyzw(x / length_squared, y / length_squared, z / length_squared, w / length_squared)
+    }
+
+    /// Spherical linear interpolation, turning at constant speed along the shortest arc from `self` at 0 to
+    /// `other` at 1
+    pub fn slerp(self, other: Quat, t: f32) -> Quat {
+        // Both signs give the same rotation, the one closest to `self` is the shortest way around
+        let (other, dot) = match self.dot(other) {
+            dot if dot < 0.0 => (-other, -dot),
+            dot => (other, dot),
+        };
+        let (from, to) = (self.to_vec4(), other.to_vec4());
+
+        if dot > SLERP_THRESHOLD {
+            // The sine below gets too small to divide by, while a straight line is as good as the arc
+            return Quat::from(from.lerp(to, t)).normalize();
+        }
+        let angle = dot.acos();
+        let sin = angle.sin();
+        Quat::from(from * (((1.0 - t) * angle).sin() / sin) + to * ((t * angle).sin() / sin))
+    }
+
+    /// Rotates a vector
+    pub fn rotate(self, v: Vec3) -> Vec3 {
+        let q = self.vector();
+        let t = q.cross(v) * 2.0;
+        v + t * self.w + q.cross(t)
+    }
+
+    /// Rotation matrix of the quaternion
+    pub fn to_mat3(self) -> Mat3 {
+        let Quat { x, y, z, w } = self;
+        let (x2, y2, z2) = (x + x, y + y, z + z);
+        let (xx, xy, xz) = (x * x2, x * y2, x * z2);
+        let (yy, yz, zz) = (y * y2, y * z2, z * z2);
+        let (wx, wy, wz) = (w * x2, w * y2, w * z2);
+
+        Mat3::from_cols(
+            Vec3::new(1.0 - (yy + zz), xy + wz, xz - wy),
+            Vec3::new(xy - wz, 1.0 - (xx + zz), yz + wx),
+            Vec3::new(xz + wy, yz - wx, 1.0 - (xx + yy)),
+        )
+    }
+
+    /// Rotation matrix of the quaternion, without translation
+    pub fn to_mat4(self) -> Mat4 {
+        Mat4::from_mat3(self.to_mat3())
+    }
+}
+
+impl Mul for Quat {
+    type Output = Quat;
+
+    /// Rotation applying `other` first and then `self`
+    fn mul(self, other: Quat) -> Quat {
+        let (a, b) = (self.vector(), other.vector());
+        let v = b * self.w + a * other.w + a.cross(b);
+        Quat::from_xyzw(v.x, v.y, v.z, self.w * other.w - a.dot(b))
+    }
+}
+
+/// Negates every component, giving the same rotation
+impl Neg for Quat {
+    type Output = Quat;
+
+    fn neg(self) -> Quat {
+        Quat::from_xyzw(-self.x, -self.y, -self.z, -self.w)
+    }
+}
+
+impl Mul<Vec3> for Quat {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        self.rotate(v)
+    }
+}
+
+impl From<Vec4> for Quat {
+    fn from(v: Vec4) -> Self {
+        Quat::from_xyzw(v.x, v.y, v.z, v.w)
+    }
+}
+
+impl From<Quat> for Mat4 {
+    fn from(q: Quat) -> Self {
+        q.to_mat4()
+    }
+}
+
+impl From<Quat> for Mat3 {
+    fn from(q: Quat) -> Self {
+        q.to_mat3()
+    }
+}
+
+/// Uploaded as a `vec4`, in x, y, z, w order
+impl AsUniformValue for Quat {
+    fn as_uniform_value(&self) -> UniformValue<'_> {
+        UniformValue::Vec4(self.to_vec4().to_array())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    use super::*;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!(a.distance(b) < 1e-5, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn axis_angle() {
+        let q = Quat::from_axis_angle(Vec3::Z, FRAC_PI_2);
+        assert_close(q * Vec3::X, Vec3::Y);
+        assert_close(q * Vec3::Y, -Vec3::X);
+        assert_close(q * Vec3::Z, Vec3::Z);
+        assert!((q.length() - 1.0).abs() < 1e-6);
+
+        // The length of the axis doesn't matter
+        let q = Quat::from_axis_angle(Vec3::new(0.0, 5.0, 0.0), PI);
+        assert_close(q * Vec3::X, -Vec3::X);
+    }
+
+    #[test]
+    fn zero_axis_is_the_identity() {
+        assert_eq!(Quat::from_axis_angle(Vec3::ZERO, 1.0), Quat::IDENTITY);
+        assert_eq!(Quat::from_xyzw(0.0, 0.0, 0.0, 0.0).normalize(), Quat::IDENTITY);
+        assert_eq!(Quat::from_xyzw(0.0, 0.0, 0.0, 0.0).inverse(), Quat::IDENTITY);
+    }
+
+    #[test]
+    fn composition_applies_the_right_rotation_first() {
+        let x = Quat::from_axis_angle(Vec3::X, FRAC_PI_2);
+        let y = Quat::from_axis_angle(Vec3::Y, FRAC_PI_2);
+        // Z turns to -Y around x, which stays -Y around y
+        assert_close((y * x) * Vec3::Z, -Vec3::Y);
+        // Z turns to X around y, which stays X around x
+        assert_close((x * y) * Vec3::Z, Vec3::X);
+        assert_close((y * x) * Vec3::Z, y * (x * Vec3::Z));
+    }
+
+    #[test]
+    fn inverse_undoes_the_rotation() {
+        let q = Quat::from_axis_angle(Vec3::new(1.0, 2.0, 3.0), 0.8);
+        let v = Vec3::new(-1.0, 0.5, 2.0);
+        assert_close(q.inverse() * (q * v), v);
+        assert_close(q.conjugate() * (q * v), v);
+
+        let doubled = Quat::from_xyzw(q.x * 2.0, q.y * 2.0, q.z * 2.0, q.w * 2.0);
+        let identity = doubled * doubled.inverse();
+        assert_close(identity.vector(), Vec3::ZERO);
+        assert!((identity.w - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn matrices_match_the_rotations() {
+        let angle = 0.7;
+        let cases = [
+            (Vec3::X, Mat4::from_rotation_x(angle)),
+            (Vec3::Y, Mat4::from_rotation_y(angle)),
+            (Vec3::Z, Mat4::from_rotation_z(angle)),
+        ];
+        for (axis, expected) in cases {
+            let matrix = Quat::from_axis_angle(axis, angle).to_mat4();
+            for (col, expected) in matrix.cols.iter().zip(expected.cols) {
+                assert!(col.distance(expected) < 1e-6, "{matrix:?} != {expected:?}");
+            }
+        }
+
+        let q = Quat::from_axis_angle(Vec3::new(1.0, -1.0, 0.5), 2.0);
+        let v = Vec3::new(0.3, 0.2, -0.7);
+        assert_close(q.to_mat3() * v, q * v);
+    }
+
+    #[test]
+    fn euler_orders() {
+        let angles = Vec3::new(0.3, -1.1, 2.0);
+        let [x, y, z] = [Vec3::X, Vec3::Y, Vec3::Z].map(|axis| Quat::from_axis_angle(axis, angles.dot(axis)));
+        let cases = [
+            (EulerOrder::Xyz, z * y * x),
+            (EulerOrder::Xzy, y * z * x),
+            (EulerOrder::Yxz, z * x * y),
+            (EulerOrder::Yzx, x * z * y),
+            (EulerOrder::Zxy, y * x * z),
+            (EulerOrder::Zyx, x * y * z),
+        ];
+        let v = Vec3::new(1.0, 2.0, 3.0);
+        for (order, expected) in cases {
+            assert_close(Quat::from_euler(order, angles) * v, expected * v);
+        }
+
+        // Z first turns Y to -X, which y then turns to Z
+        let q = Quat::from_euler(EulerOrder::Zyx, Vec3::new(0.0, FRAC_PI_2, FRAC_PI_2));
+        assert_close(q * Vec3::Y, Vec3::Z);
+    }
+
+    #[test]
+    fn from_mat3_round_trips() {
+        // Angles close to each branch of the conversion
+        for (axis, angle) in [(Vec3::Y, 0.5), (Vec3::X, 3.0), (Vec3::Y, 3.0), (Vec3::Z, 3.0), (Vec3::new(1.0, 1.0, -1.0), 2.5)] {
+            let q = Quat::from_axis_angle(axis, angle);
+            let back = Quat::from_mat3(q.to_mat3());
+            assert!(q.dot(back).abs() > 1.0 - 1e-6, "{q:?} != {back:?}");
+        }
+    }
+
+    #[test]
+    fn look_to() {
+        let q = Quat::look_to(Vec3::new(-3.0, 0.0, 0.0), Vec3::Y);
+        assert_close(q * Vec3::Z, -Vec3::X);
+        assert_close(q * Vec3::Y, Vec3::Y);
+        assert_close(q * Vec3::X, Vec3::Z);
+
+        let q = Quat::look_at(Vec3::ONE, Vec3::new(1.0, 1.0, 5.0), Vec3::Y);
+        assert!(q.dot(Quat::IDENTITY).abs() > 1.0 - 1e-6);
+
+        // The view matrix undoes the orientation of the camera
+        let direction = Vec3::new(0.3, -0.5, 0.8);
+        let view = Mat4::look_to(Vec3::ZERO, direction, Vec3::Y);
+        let v = Vec3::new(0.1, 0.7, -0.4);
+        assert_close(view.transform_vector(Quat::look_to(direction, Vec3::Y) * v), v);
+
+        // Looking straight up still gives a rotation
+        let q = Quat::look_to(Vec3::Y, Vec3::Y);
+        assert_close(q * Vec3::Z, Vec3::Y);
+        assert!((q.length() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn slerp() {
+        let from = Quat::IDENTITY;
+        let to = Quat::from_axis_angle(Vec3::Y, FRAC_PI_2);
+        assert_eq!(from.slerp(to, 0.0), from);
+        assert_close(from.slerp(to, 1.0) * Vec3::Z, to * Vec3::Z);
+
+        // Constant speed: a third of the way is a third of the angle
+        let third = from.slerp(to, 1.0 / 3.0);
+        assert_close(third * Vec3::X, Quat::from_axis_angle(Vec3::Y, PI / 6.0) * Vec3::X);
+        assert!((third.length() - 1.0).abs() < 1e-6);
+
+        // The same rotation with the other sign still takes the short way
+        let halfway = from.slerp(-to, 0.5);
+        assert_close(halfway * Vec3::X, Quat::from_axis_angle(Vec3::Y, PI / 4.0) * Vec3::X);
+
+        // Nearly equal rotations
+        let close = Quat::from_axis_angle(Vec3::X, 1e-4);
+        assert!((from.slerp(close, 0.5).length() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn uniform() {
+        assert!(matches!(Quat::IDENTITY.as_uniform_value(), UniformValue::Vec4([0.0, 0.0, 0.0, 1.0])));
+    }
+}
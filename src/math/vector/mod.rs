@@ -0,0 +1,370 @@
+//! Vectors of 2, 3 and 4 components
+
+use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use glium::uniforms::{AsUniformValue, UniformValue};
+
+/// Lengths under which a vector is considered to have no direction
+const EPSILON: f32 = 1e-6;
+
+/// Implements the operations shared by every vector type, component by component
+macro_rules! impl_vector {
+    ($name: ident, $uniform: ident, $n: literal, $($field: ident),+) => {
+        impl $name {
+            /// Vector with every component set to 0
+            pub const ZERO: $name = $name { $($field: 0.0),+ };
+            /// Vector with every component set to 1
+            pub const ONE: $name = $name { $($field: 1.0),+ };
+
+            /// Creates a vector from its components
+            pub const fn new($($field: f32),+) -> $name {
+                $name { $($field),+ }
+            }
+
+            /// Vector with every component set to `value`
+            pub const fn splat(value: f32) -> $name {
+                $name { $($field: value),+ }
+            }
+
+            /// Sum of the products of the matching components
+            pub fn dot(self, other: $name) -> f32 {
+                0.0 $(+ self.$field * other.$field)+
+            }
+
+            /// Square of the length, cheaper than the length when only comparing
+            pub fn length_squared(self) -> f32 {
+                self.dot(self)
+            }
+
+            /// Euclidean length
+            pub fn length(self) -> f32 {
+                self.length_squared().sqrt()
+            }
+
+            /// Distance between two points
+            pub fn distance(self, other: $name) -> f32 {
+                (self - other).length()
+            }
+
+            /// Vector of length 1 in the same direction, `None` when the vector is too short to have one
+            pub fn try_normalize(self) -> Option<$name> {
+                let length = self.length();
+                (length > EPSILON && length.is_finite()).then(|| self / length)
+            }
+
+            /// Vector of length 1 in the same direction, zero when the vector is too short to have one
+            pub fn normalize(self) -> $name {
+                self.try_normalize().unwrap_or($name::ZERO)
+            }
+
+            /// Vector of length 1 in the same direction, `fallback` when the vector is too short to have one
+            pub fn normalize_or(self, fallback: $name) -> $name {
+                self.try_normalize().unwrap_or(fallback)
+            }
+
+            /// Linear interpolation, `self` at 0 and `other` at 1
+            pub fn lerp(self, other: $name, t: f32) -> $name {
+                self + (other - self) * t
+            }
+
+            /// Smallest of each pair of components
+            pub fn min(self, other: $name) -> $name {
+                $name { $($field: self.$field.min(other.$field)),+ }
+            }
+
+            /// Largest of each pair of components
+            pub fn max(self, other: $name) -> $name {
+                $name { $($field: self.$field.max(other.$field)),+ }
+            }
+
+            /// Components as an array
+            pub fn to_array(self) -> [f32; $n] {
+                [$(self.$field),+]
+            }
+        }
+
+        impl From<[f32; $n]> for $name {
+            fn from([$($field),+]: [f32; $n]) -> Self {
+                $name { $($field),+ }
+            }
+        }
+
+        impl From<$name> for [f32; $n] {
+            fn from(vector: $name) -> Self {
+                vector.to_array()
+            }
+        }
+
+        impl Index<usize> for $name {
+            type Output = f32;
+
+            fn index(&self, index: usize) -> &f32 {
+                [$(&self.$field),+][index]
+            }
+        }
+
+        impl IndexMut<usize> for $name {
+            fn index_mut(&mut self, index: usize) -> &mut f32 {
+                let $name { $($field),+ } = self;
+                [$($field),+].into_iter().nth(index).expect("index out of bounds")
+            }
+        }
+
+        impl Add for $name {
+            type Output = $name;
+
+            fn add(self, other: $name) -> $name {
+                $name { $($field: self.$field + other.$field),+ }
+            }
+        }
+
+        impl Sub for $name {
+            type Output = $name;
+
+            fn sub(self, other: $name) -> $name {
+                $name { $($field: self.$field - other.$field),+ }
+            }
+        }
+
+        /// Component by component product
+        impl Mul for $name {
+            type Output = $name;
+
+            fn mul(self, other: $name) -> $name {
+                $name { $($field: self.$field * other.$field),+ }
+            }
+        }
+
+        impl Mul<f32> for $name {
+            type Output = $name;
+
+            fn mul(self, scalar: f32) -> $name {
+                $name { $($field: self.$field * scalar),+ }
+            }
+        }
+
+        impl Mul<$name> for f32 {
+            type Output = $name;
+
+            fn mul(self, vector: $name) -> $name {
+                vector * self
+            }
+        }
+
+        impl Div<f32> for $name {
+            type Output = $name;
+
+            fn div(self, scalar: f32) -> $name {
+                $name { $($field: self.$field / scalar),+ }
+            }
+        }
+
+        impl Neg for $name {
+            type Output = $name;
+
+            fn neg(self) -> $name {
+                $name { $($field: -self.$field),+ }
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, other: $name) {
+                *self = *self + other;
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: $name) {
+                *self = *self - other;
+            }
+        }
+
+        impl MulAssign<f32> for $name {
+            fn mul_assign(&mut self, scalar: f32) {
+                *self = *self * scalar;
+            }
+        }
+
+        impl DivAssign<f32> for $name {
+            fn div_assign(&mut self, scalar: f32) {
+                *self = *self / scalar;
+            }
+        }
+
+        impl AsUniformValue for $name {
+            fn as_uniform_value(&self) -> UniformValue<'_> {
+                UniformValue::$uniform(self.to_array())
+            }
+        }
+    };
+}
+
+/// Vector of 2 components, like texture coordinates
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Vec2 {
+    /// First component
+    pub x: f32,
+    /// Second component
+    pub y: f32,
+}
+
+/// Vector of 3 components, a position or a direction in space
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Vec3 {
+    /// First component
+    pub x: f32,
+    /// Second component
+    pub y: f32,
+    /// Third component
+    pub z: f32,
+}
+
+/// Vector of 4 components, a point (w = 1) or a direction (w = 0) in homogeneous coordinates
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Vec4 {
+    /// First component
+    pub x: f32,
+    /// Second component
+    pub y: f32,
+    /// Third component
+    pub z: f32,
+    /// Fourth component
+    pub w: f32,
+}
+
+impl_vector!(Vec2, Vec2, 2, x, y);
+impl_vector!(Vec3, Vec3, 3, x, y, z);
+impl_vector!(Vec4, Vec4, 4, x, y, z, w);
+
+impl Vec2 {
+    /// Adds a third component
+    pub fn extend(self, z: f32) -> Vec3 {
+        Vec3::new(self.x, self.y, z)
+    }
+}
+
+impl Vec3 {
+    /// Unit vector along the x axis, to the right
+    pub const X: Vec3 = Vec3::new(1.0, 0.0, 0.0);
+    /// Unit vector along the y axis, up
+    pub const Y: Vec3 = Vec3::new(0.0, 1.0, 0.0);
+    /// Unit vector along the z axis, forward
+    pub const Z: Vec3 = Vec3::new(0.0, 0.0, 1.0);
+
+    /// Vector perpendicular to both, following the right-hand rule, zero when they are parallel
+    pub fn cross(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    /// Some unit vector perpendicular to this one, which is expected to be of length 1
+    pub fn any_perpendicular(self) -> Vec3 {
+        let axis = if self.x.abs() < 0.9 { Vec3::X } else { Vec3::Y };
+        axis.cross(self).normalize()
+    }
+
+    /// Adds a fourth component, 1 for a point and 0 for a direction
+    pub fn extend(self, w: f32) -> Vec4 {
+        Vec4::new(self.x, self.y, self.z, w)
+    }
+
+    /// Drops the third component
+    pub fn truncate(self) -> Vec2 {
+        Vec2::new(self.x, self.y)
+    }
+}
+
+impl Vec4 {
+    /// Drops the fourth component
+    pub fn truncate(self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic() {
+        let a = Vec3::new(1.0, 2.0, 3.0);
+        let b = Vec3::new(4.0, -5.0, 6.0);
+        assert_eq!(a + b, Vec3::new(5.0, -3.0, 9.0));
+        assert_eq!(a - b, Vec3::new(-3.0, 7.0, -3.0));
+        assert_eq!(a * b, Vec3::new(4.0, -10.0, 18.0));
+        assert_eq!(a * 2.0, Vec3::new(2.0, 4.0, 6.0));
+        assert_eq!(2.0 * a, a * 2.0);
+        assert_eq!(a / 2.0, Vec3::new(0.5, 1.0, 1.5));
+        assert_eq!(-a, Vec3::new(-1.0, -2.0, -3.0));
+
+        let mut c = a;
+        c += b;
+        c -= a;
+        c *= 3.0;
+        c /= 3.0;
+        assert_eq!(c, b);
+    }
+
+    #[test]
+    fn dot_and_length() {
+        assert_eq!(Vec3::new(1.0, 2.0, 3.0).dot(Vec3::new(4.0, -5.0, 6.0)), 12.0);
+        assert_eq!(Vec2::new(3.0, 4.0).length(), 5.0);
+        assert_eq!(Vec4::new(1.0, 1.0, 1.0, 1.0).length(), 2.0);
+        assert_eq!(Vec3::new(1.0, 2.0, 2.0).distance(Vec3::ZERO), 3.0);
+    }
+
+    #[test]
+    fn cross_follows_the_right_hand_rule() {
+        assert_eq!(Vec3::X.cross(Vec3::Y), Vec3::Z);
+        assert_eq!(Vec3::Y.cross(Vec3::Z), Vec3::X);
+        assert_eq!(Vec3::Z.cross(Vec3::X), Vec3::Y);
+        assert_eq!(Vec3::Y.cross(Vec3::X), -Vec3::Z);
+        assert_eq!(Vec3::X.cross(Vec3::X * 2.0), Vec3::ZERO);
+    }
+
+    #[test]
+    fn normalize() {
+        assert_eq!(Vec3::new(0.0, 3.0, 4.0).normalize(), Vec3::new(0.0, 0.6, 0.8));
+        assert_eq!(Vec2::new(-2.0, 0.0).normalize(), Vec2::new(-1.0, 0.0));
+    }
+
+    #[test]
+    fn normalize_zero_length() {
+        assert_eq!(Vec3::ZERO.normalize(), Vec3::ZERO);
+        assert_eq!(Vec3::ZERO.try_normalize(), None);
+        assert_eq!(Vec3::splat(1e-9).normalize_or(Vec3::Y), Vec3::Y);
+        assert_eq!(Vec3::new(f32::NAN, 0.0, 0.0).try_normalize(), None);
+        assert_eq!(Vec4::ZERO.normalize(), Vec4::ZERO);
+    }
+
+    #[test]
+    fn conversions() {
+        let v = Vec3::from([1.0, 2.0, 3.0]);
+        assert_eq!(v, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(<[f32; 3]>::from(v), [1.0, 2.0, 3.0]);
+        assert_eq!(v.extend(1.0), Vec4::new(1.0, 2.0, 3.0, 1.0));
+        assert_eq!(v.extend(1.0).truncate(), v);
+        assert_eq!(v.truncate(), Vec2::new(1.0, 2.0));
+        assert!(matches!(v.as_uniform_value(), UniformValue::Vec3([1.0, 2.0, 3.0])));
+    }
+
+    #[test]
+    fn indexing() {
+        let mut v = Vec4::new(1.0, 2.0, 3.0, 4.0);
+        assert_eq!([v[0], v[1], v[2], v[3]], [1.0, 2.0, 3.0, 4.0]);
+        v[2] = 7.0;
+        assert_eq!(v.z, 7.0);
+    }
+
+    #[test]
+    fn lerp_min_max() {
+        let a = Vec2::new(0.0, 10.0);
+        let b = Vec2::new(10.0, 0.0);
+        assert_eq!(a.lerp(b, 0.25), Vec2::new(2.5, 7.5));
+        assert_eq!(a.min(b), Vec2::ZERO);
+        assert_eq!(a.max(b), Vec2::splat(10.0));
+    }
+}
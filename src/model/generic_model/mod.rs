@@ -1,62 +1,145 @@
-use glium::{Display, DrawParameters, Frame, IndexBuffer, Surface, VertexBuffer};
+//! Models drawn with the lit and shadowed program of the renderer
+
+use glium::{DrawParameters, IndexBuffer, Surface, VertexBuffer};
+use glium::backend::Facade;
+use glium::index::PrimitiveType;
 
 use crate::assets::{
+    bounds::Aabb,
+    load_tex::load_texture,
     transform::*,
     vertex::*,
 };
-use crate::model::{get_light, get_light_rotation_matrix, Model, ModelData};
-use crate::model::get_program;
+use crate::model::{ImportOptions, Model, ModelData, ModelError, SubMesh};
+use crate::model::material::Material;
+use crate::model::renderer::Renderer;
 use crate::model::model_parser::parse_model;
+use crate::model::shadow::depth_parameters;
+
+/// Normal and the two axes spanning each face of the placeholder cube
+const CUBE_FACES: [([f32; 3], [f32; 3], [f32; 3]); 6] = [
+    ([1., 0., 0.], [0., 0., -1.], [0., 1., 0.]),
+    ([-1., 0., 0.], [0., 0., 1.], [0., 1., 0.]),
+    ([0., 1., 0.], [1., 0., 0.], [0., 0., -1.]),
+    ([0., -1., 0.], [1., 0., 0.], [0., 0., 1.]),
+    ([0., 0., 1.], [1., 0., 0.], [0., 1., 0.]),
+    ([0., 0., -1.], [-1., 0., 0.], [0., 1., 0.]),
+];
 
+/// A model loaded from a wavefront .obj file, or built from vertices
 pub struct GenericModel {
+    /// Buffers and materials of the model
     pub model_data: ModelData,
 }
 
 impl GenericModel {
 
-    /// Creates a new GenericModel from given the indices, normals and vertices
-    pub fn new(display: &Display, vertices: &Vec<Vertex>, indices: &Vec<u32>, normals: &Vec<Normal>) -> GenericModel {
+    /// Creates a new GenericModel from given the indices, normals and vertices, with the default material
+    pub fn new<F: Facade>(display: &F, vertices: &[Vertex], indices: &[u32], normals: &[Normal]) -> Result<GenericModel, ModelError> {
         let model_data = ModelData {
-            vertices: VertexBuffer::new(display, vertices).unwrap(),
-            indices: IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, indices).unwrap(),
-            normals: VertexBuffer::new(display, normals).unwrap(),
+            vertices: VertexBuffer::new(display, vertices)?,
+            normals: VertexBuffer::new(display, normals)?,
+            submeshes: vec![SubMesh {
+                indices: IndexBuffer::new(display, PrimitiveType::TrianglesList, indices)?,
+                material: Material::default(),
+            }],
+            bounds: Aabb::from_points(vertices.iter().map(|v| v.position)).unwrap_or_default(),
         };
-        GenericModel {
+        Ok(GenericModel {
             model_data,
-        }
+        })
     }
 
-    /// Creates a new GenericModel from given the path to the model file
-    pub fn from_obj(display: &Display, obj_src: String) -> Self {
+    /// Creates a new GenericModel from given the path to the model file, with one submesh per material
+    pub fn from_obj<F: Facade>(display: &F, obj_src: String, options: &ImportOptions) -> Result<Self, ModelError> {
+
+        let parsed = parse_model(&obj_src, options)?;
 
-        let (vertices, indices, normals) = parse_model(&obj_src);
+        let submeshes = parsed.submeshes
+            .into_iter()
+            .map(|submesh| {
+                let parsed_material = submesh.material;
+                let default = Material::default();
+                let diffuse_map = parsed_material.diffuse_map.and_then(|path| {
+                    load_texture(display, &path)
+                        .map_err(|e| eprintln!("Could not load texture {}: {e}", path.display()))
+                        .ok()
+                });
+                Ok(SubMesh {
+                    indices: IndexBuffer::new(display, PrimitiveType::TrianglesList, &submesh.indices)?,
+                    material: Material {
+                        diffuse: parsed_material.diffuse.unwrap_or(default.diffuse),
+                        specular: parsed_material.specular.unwrap_or(default.specular),
+                        shininess: parsed_material.shininess.unwrap_or(default.shininess),
+                        diffuse_map,
+                    },
+                })
+            })
+            .collect::<Result<Vec<_>, ModelError>>()?;
 
-        GenericModel {
+        Ok(GenericModel {
             model_data: ModelData {
-                vertices: VertexBuffer::new(display, &vertices[..]).unwrap(),
-                indices: IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &indices[..]).unwrap(),
-                normals: VertexBuffer::new(display, &normals[..]).unwrap(),
+                vertices: VertexBuffer::new(display, &parsed.vertices)?,
+                normals: VertexBuffer::new(display, &parsed.normals)?,
+                submeshes,
+                bounds: parsed.bounds,
+            }
+        })
+    }
+
+    /// Creates a cube of the given size resting on the origin, shown in place of models that failed to load
+    pub fn placeholder<F: Facade>(display: &F, size: f32) -> Result<Self, ModelError> {
+        let half = size / 2.;
+        let mut vertices = Vec::with_capacity(24);
+        let mut normals = Vec::with_capacity(24);
+        let mut indices = Vec::with_capacity(36);
+
+        for (normal, u, v) in CUBE_FACES {
+            let first = vertices.len() as u32;
+            for (su, sv) in [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)] {
+                let position = [0, 1, 2].map(|i| (normal[i] + su * u[i] + sv * v[i]) * half + if i == 1 { half } else { 0. });
+                vertices.push(Vertex { position, tex_coords: [(su + 1.) / 2., (sv + 1.) / 2.] });
+                normals.push(Normal { normal });
             }
+            indices.extend([0, 1, 2, 2, 3, 0].map(|i| first + i));
         }
+
+        GenericModel::new(display, &vertices, &indices, &normals)
     }
 }
 
 impl Model for GenericModel {
-    /// Draws the model
-    fn draw(&self, target: &mut Frame, params: &DrawParameters, transform: &Transform) {
+    /// Draws the model, one draw call per submesh
+    fn draw<S: Surface>(&self, target: &mut S, renderer: &Renderer, params: &DrawParameters, transform: &Transform) {
+        for submesh in &self.model_data.submeshes {
+            let material = &submesh.material;
+            let view = transform.get_view();
+            let uniforms = renderer.lit(view, uniform! {
+                model: transform.get_model(),
+                view: view,
+                projection: transform.get_projection(),
+                normal_matrix: transform.get_normal_matrix(),
+                tex: material.diffuse_map.as_ref().unwrap_or_else(|| transform.get_texture()),
+                material_diffuse: material.diffuse,
+                material_specular: material.specular,
+                material_shininess: material.shininess,
+            });
+            target.draw((&self.model_data.vertices, &self.model_data.normals), &submesh.indices, renderer.program(), &uniforms, params).unwrap();
+        }
+    }
+
+    /// Draws the depth of every submesh, skipped when no light casts shadows
+    fn draw_shadow<S: Surface>(&self, target: &mut S, renderer: &Renderer, transform: &Transform) {
+        let Some((_, light_space)) = renderer.shadow_caster() else {
+            return;
+        };
         let uniforms = uniform! {
-            translation: transform.get_translation(),
-            scale: transform.get_scaling(),
-            rotation: transform.get_rotation(),
-            self_rotation: transform.get_self_rotation(),
-            view: transform.get_view(),
-            perspective: transform.get_perspective(),
-            light: get_light(),
-            light_rotation: get_light_rotation_matrix(),
-            tex: transform.get_texture(),
+            model: transform.get_model(),
+            light_space: light_space,
         };
-        target.draw((&self.model_data.vertices, &self.model_data.normals), &self.model_data.indices, get_program().unwrap(), &uniforms, params).unwrap();
+        let params = depth_parameters();
+        for submesh in &self.model_data.submeshes {
+            target.draw(&self.model_data.vertices, &submesh.indices, renderer.shadow_program(), &uniforms, &params).unwrap();
+        }
     }
 }
-
-
@@ -0,0 +1,193 @@
+//! Light sources and the uniforms uploading them to the shaders
+
+use glium::uniforms::{UniformValue, Uniforms};
+use serde::Deserialize;
+
+use crate::math::Mat4;
+use crate::rotate;
+
+/// Maximum number of lights the shader accumulates, extra lights are ignored
+pub const MAX_LIGHTS: usize = 8;
+
+/// Shape of a light source
+#[derive(Deserialize, Copy, Clone, Debug)]
+pub enum LightKind {
+    /// Light coming from infinitely far away, like the sun
+    Directional {
+        /// Direction the light travels in
+        direction: [f32; 3],
+    },
+    /// Light shining in all directions from a point
+    Point {
+        /// Position of the light, in world units, relative to the object it is attached to if any
+        position: [f32; 3],
+        /// Constant, linear and quadratic attenuation factors
+        #[serde(default = "default_attenuation")]
+        attenuation: [f32; 3],
+    },
+    /// Light shining in a cone from a point
+    Spot {
+        /// Position of the light, in world units, relative to the object it is attached to if any
+        position: [f32; 3],
+        /// Direction the cone points to, turning along with the object it is attached to if any
+        direction: [f32; 3],
+        /// Constant, linear and quadratic attenuation factors
+        #[serde(default = "default_attenuation")]
+        attenuation: [f32; 3],
+        /// Angle from the axis, in radians, inside which the light is at full intensity
+        inner_angle: f32,
+        /// Angle from the axis, in radians, outside which there is no light
+        outer_angle: f32,
+    },
+}
+
+fn default_attenuation() -> [f32; 3] {
+    [1.0, 0.0, 1.0]
+}
+
+/// A light source of the scene
+#[derive(Deserialize, Clone, Debug)]
+pub struct Light {
+    /// Shape of the light
+    pub kind: LightKind,
+    /// Color of the light, multiplied by the colors of the surfaces
+    #[serde(default = "default_color")]
+    pub color: [f32; 3],
+    /// Factor the color is multiplied by
+    #[serde(default = "default_intensity")]
+    pub intensity: f32,
+    /// Name of the object the light follows, like the headlights of a vehicle
+    #[serde(default)]
+    pub attach: Option<String>,
+}
+
+fn default_color() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_intensity() -> f32 {
+    1.0
+}
+
+impl Default for Light {
+    /// White sun shining diagonally, as the scene was lit before lights were configurable
+    fn default() -> Self {
+        Light {
+            kind: LightKind::Directional { direction: [-1.0, -1.0, -1.0] },
+            color: default_color(),
+            intensity: default_intensity(),
+            attach: None,
+        }
+    }
+}
+
+impl Light {
+    /// Light moved from the frame of the object it is attached to into the world, `frame` being the world
+    /// matrix of that frame
+    pub fn placed(&self, frame: [[f32; 4]; 4]) -> Light {
+        let frame = Mat4::from(frame);
+        let point = |position: [f32; 3]| frame.transform_point(position.into()).into();
+        let vector = |direction: [f32; 3]| frame.transform_vector(direction.into()).into();
+
+        let kind = match self.kind {
+            LightKind::Directional { direction } => LightKind::Directional { direction: vector(direction) },
+            LightKind::Point { position, attenuation } => LightKind::Point { position: point(position), attenuation },
+            LightKind::Spot { position, direction, attenuation, inner_angle, outer_angle } => LightKind::Spot {
+                position: point(position),
+                direction: vector(direction),
+                attenuation,
+                inner_angle,
+                outer_angle,
+            },
+        };
+        Light { kind, ..self.clone() }
+    }
+
+    /// Direction a directional light travels in once rotated by `rotation` radians around the x axis
+    pub fn rotated_direction(&self, rotation: f32) -> Option<[f32; 3]> {
+        match self.kind {
+            LightKind::Directional { direction } => Some(rotate_x(direction, rotation)),
+            _ => None,
+        }
+    }
+}
+
+/// Adds the lights of the scene to the uniforms of a draw call.
+///
+/// Each light is uploaded as an element of the `lights` array of structs declared in the fragment shader,
+/// with directional lights rotated by `rotation` radians around the x axis. Positions and directions are
+/// moved into view space, where the lit shader computes the lighting.
+pub struct LightUniforms<'a, U: Uniforms> {
+    /// Uniforms of the draw call the lights are added to
+    pub base: U,
+    /// Color of the ambient light
+    pub ambient: [f32; 3],
+    /// Lights of the scene, only the first `MAX_LIGHTS` are used
+    pub lights: &'a [Light],
+    /// Rotation of the directional lights around the x axis, in radians
+    pub rotation: f32,
+    /// View matrix of the camera the lights are seen through
+    pub view: [[f32; 4]; 4],
+}
+
+impl<U: Uniforms> Uniforms for LightUniforms<'_, U> {
+    fn visit_values<'b, F: FnMut(&str, UniformValue<'b>)>(&'b self, mut output: F) {
+        self.base.visit_values(&mut output);
+
+        output("ambient_light", UniformValue::Vec3(self.ambient));
+
+        let view = Mat4::from(self.view);
+        let lights = &self.lights[..self.lights.len().min(MAX_LIGHTS)];
+        output("light_count", UniformValue::SignedInt(lights.len() as i32));
+
+        for (i, light) in lights.iter().enumerate() {
+            let (kind, position, direction, attenuation, cutoffs) = match light.kind {
+                LightKind::Directional { direction } =>
+                    (0, [0.0; 3], rotate_x(direction, self.rotation), [1.0, 0.0, 0.0], [-1.0, -1.0]),
+                LightKind::Point { position, attenuation } =>
+                    (1, position, [0.0; 3], attenuation, [-1.0, -1.0]),
+                LightKind::Spot { position, direction, attenuation, inner_angle, outer_angle } =>
+                    (2, position, direction, attenuation, [inner_angle.cos(), outer_angle.cos()]),
+            };
+            let position = view.transform_point(position.into()).into();
+            let direction = view.transform_vector(direction.into()).into();
+            output(&format!("lights[{i}].kind"), UniformValue::SignedInt(kind));
+            output(&format!("lights[{i}].position"), UniformValue::Vec3(position));
+            output(&format!("lights[{i}].direction"), UniformValue::Vec3(direction));
+            output(&format!("lights[{i}].color"), UniformValue::Vec3(light.color));
+            output(&format!("lights[{i}].intensity"), UniformValue::Float(light.intensity));
+            output(&format!("lights[{i}].attenuation"), UniformValue::Vec3(attenuation));
+            output(&format!("lights[{i}].inner_cutoff"), UniformValue::Float(cutoffs[0]));
+            output(&format!("lights[{i}].outer_cutoff"), UniformValue::Float(cutoffs[1]));
+        }
+    }
+}
+
+/// Rotates a vector around the x axis
+fn rotate_x(vector: [f32; 3], angle: f32) -> [f32; 3] {
+    let matrix = rotate!(angle, x);
+    [0, 1, 2].map(|row| (0..3).map(|column| matrix[column][row] * vector[column]).sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{Quat, Vec3};
+
+    #[test]
+    fn placed_lights_follow_the_frame() {
+        let light = Light {
+            kind: LightKind::Spot { position: [0.0, 0.0, 1.0], direction: [0.0, 0.0, 1.0], attenuation: [1.0, 0.0, 0.0], inner_angle: 0.3, outer_angle: 0.5 },
+            attach: Some("bus".to_string()),
+            ..Default::default()
+        };
+        // Turned a quarter turn around y, then moved along x
+        let frame = Mat4::from_translation(Vec3::X) * Quat::from_axis_angle(Vec3::Y, std::f32::consts::FRAC_PI_2).to_mat4();
+
+        let LightKind::Spot { position, direction, .. } = light.placed(frame.into()).kind else {
+            panic!("the kind of the light changed");
+        };
+        assert!((Vec3::from(position) - Vec3::new(2.0, 0.0, 0.0)).length() < 1e-6);
+        assert!((Vec3::from(direction) - Vec3::X).length() < 1e-6);
+    }
+}
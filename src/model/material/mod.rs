@@ -0,0 +1,26 @@
+//! Materials of the models
+
+use glium::texture::SrgbTexture2d;
+
+/// Surface properties of a part of a model, as read from a .mtl file
+pub struct Material {
+    /// Diffuse color (Kd), multiplied with the texture
+    pub diffuse: [f32; 3],
+    /// Specular color (Ks)
+    pub specular: [f32; 3],
+    /// Specular exponent (Ns)
+    pub shininess: f32,
+    /// Diffuse texture (map_Kd), used instead of the texture of the transform when present
+    pub diffuse_map: Option<SrgbTexture2d>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            diffuse: [1.0, 1.0, 1.0],
+            specular: [1.0, 1.0, 1.0],
+            shininess: 16.0,
+            diffuse_map: None,
+        }
+    }
+}
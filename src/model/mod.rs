@@ -1,79 +1,136 @@
-use glium::{Display, IndexBuffer, Program, VertexBuffer};
+//! Models of the scene, loaded from wavefront .obj files, and the renderer drawing them
+
+use std::{fmt, io};
+
+use glium::{DrawParameters, IndexBuffer, Surface, VertexBuffer};
+use obj::ObjError;
+use serde::Deserialize;
 
 use crate::assets::{
-    matrices::*,
+    bounds::Aabb,
     transform::*,
     vertex::*,
 };
-use crate::rotate;
+use crate::model::material::Material;
+use crate::model::renderer::Renderer;
 
 pub mod generic_model;
-mod model_parser;
-
-const VERT_SHADER: &str = include_str!("shaders/shader.vert");
-const FRAG_SHADER: &str = include_str!("shaders/shader.frag");
-
-static mut PROGRAM: Option<Program> = None;
-pub fn set_program(display: &Display) {
-    unsafe {
-        if let None = PROGRAM {
-            PROGRAM = Some(
-                Program::from_source(
-                    display,
-                    VERT_SHADER,
-                    FRAG_SHADER,
-                    None
-                ).unwrap(),
-            );
-        }
-    }
+pub mod light;
+pub mod material;
+pub mod model_parser;
+pub mod renderer;
+pub mod shadow;
+pub mod skybox;
+
+/// Axis pointing up in a model file
+#[derive(Deserialize, Copy, Clone, Debug, Default, PartialEq)]
+pub enum UpAxis {
+    /// Y up, like the scene
+    #[default]
+    Y,
+    /// Converted to Y up when loading
+    Z,
+}
+
+/// Options applied to the vertices of a model when it is loaded
+#[derive(Deserialize, Copy, Clone, Debug)]
+#[serde(default)]
+pub struct ImportOptions {
+    /// Factor converting the units of the file to world units
+    pub unit_scale: f32,
+    /// Axis pointing up in the file
+    pub up_axis: UpAxis,
+    /// Centres the model on the origin and scales it to fit in a box of size 1
+    pub normalize: bool,
 }
 
-pub fn get_program() -> Option<&'static Program> {
-    unsafe {
-        PROGRAM.as_ref()
+impl Default for ImportOptions {
+    fn default() -> Self {
+        ImportOptions {
+            unit_scale: 1.0,
+            up_axis: UpAxis::Y,
+            normalize: false,
+        }
     }
 }
 
-static mut LIGHT: Light = [1.0, 1.0, 1.0f32];
-static mut LIGHT_ROTATION: f32 = 0.0;
+/// Errors that can happen while loading a model
+#[derive(Debug)]
+pub enum ModelError {
+    /// The model file could not be opened
+    Io(io::Error),
+    /// The model file is not a valid wavefront .obj file
+    Obj(ObjError),
+    /// The model has more vertices than its indices can address
+    IndexOverflow,
+    /// The vertex buffer could not be created on the GPU
+    VertexBuffer(glium::vertex::BufferCreationError),
+    /// The index buffer could not be created on the GPU
+    IndexBuffer(glium::index::BufferCreationError),
+}
 
-pub fn set_light_rotation(light_rotation: f32) {
-    unsafe {
-        LIGHT_ROTATION = light_rotation;
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelError::Io(e) => write!(f, "could not open model: {e}"),
+            ModelError::Obj(e) => write!(f, "could not parse model: {e}"),
+            ModelError::IndexOverflow => write!(f, "model has too many vertices to be indexed"),
+            ModelError::VertexBuffer(e) => write!(f, "could not create vertex buffer: {e}"),
+            ModelError::IndexBuffer(e) => write!(f, "could not create index buffer: {e}"),
+        }
     }
 }
 
-pub fn get_light_rotation_matrix() -> [[f32; 4]; 4] {
-    unsafe{
-        rotate!(LIGHT_ROTATION.clone(), x)
+impl std::error::Error for ModelError {}
+
+impl From<io::Error> for ModelError {
+    fn from(e: io::Error) -> Self {
+        ModelError::Io(e)
     }
 }
 
-pub fn get_light_rotation() -> f32 {
-    unsafe {
-        LIGHT_ROTATION.clone()
+impl From<ObjError> for ModelError {
+    fn from(e: ObjError) -> Self {
+        ModelError::Obj(e)
     }
 }
 
-pub fn set_light(light: Light) {
-    unsafe {
-        LIGHT = light;
+impl From<glium::vertex::BufferCreationError> for ModelError {
+    fn from(e: glium::vertex::BufferCreationError) -> Self {
+        ModelError::VertexBuffer(e)
     }
 }
 
-pub fn get_light() -> Light {
-    unsafe {
-        LIGHT.clone()
+impl From<glium::index::BufferCreationError> for ModelError {
+    fn from(e: glium::index::BufferCreationError) -> Self {
+        ModelError::IndexBuffer(e)
     }
 }
 
+/// Buffers of a model, uploaded to the GPU
 pub struct ModelData {
+    /// Positions and texture coordinates
     pub vertices: VertexBuffer<Vertex>,
-    pub indices: IndexBuffer<u32>,
+    /// Normals, in the same order as `vertices`
     pub normals: VertexBuffer<Normal>,
+    /// Parts of the model drawn with a different material each
+    pub submeshes: Vec<SubMesh>,
+    /// Bounds of the vertices, in model space
+    pub bounds: Aabb,
+}
+
+/// Part of a model drawn with a single material
+pub struct SubMesh {
+    /// Triangles of the submesh, as indices into the vertices of the model
+    pub indices: IndexBuffer<u32>,
+    /// Colors and texture of the submesh
+    pub material: Material,
 }
 
+/// Anything that can be drawn into the scene
 pub trait Model {
-    fn draw(&self, _: &mut glium::Frame, params: &glium::DrawParameters, transform: &Transform);
+    /// Draws the model, lit and shadowed, placed and projected by `transform`
+    fn draw<S: Surface>(&self, target: &mut S, renderer: &Renderer, params: &DrawParameters, transform: &Transform);
+    /// Draws the depth of the model, as seen from the light casting shadows, into the shadow map
+    fn draw_shadow<S: Surface>(&self, target: &mut S, renderer: &Renderer, transform: &Transform);
 }
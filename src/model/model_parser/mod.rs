@@ -1,42 +1,314 @@
+//! Wavefront .obj and .mtl parsing, independent of any OpenGL context
+
 extern crate obj;
 
-use std::{env, fs};
-use std::fs::DirBuilder;
+use std::collections::HashMap;
+use std::fs;
 use std::io::BufReader;
-use std::io::prelude::*;
 use std::path::{Path, PathBuf};
-use obj::{load_obj, Obj, TexturedVertex};
 
-use crate::assets::vertex::{Normal, Vertex};
+use obj::raw::{parse_mtl, parse_obj, RawObj};
+use obj::raw::material::MtlColor;
+use obj::raw::object::Polygon;
+
+use crate::assets::{
+    bounds::Aabb,
+    vertex::{Normal, Vertex},
+};
+use crate::model::{ImportOptions, ModelError, UpAxis};
+
+/// Material of a submesh, with the texture still to be loaded
+pub(crate) struct ParsedMaterial {
+    pub diffuse: Option<[f32; 3]>,
+    pub specular: Option<[f32; 3]>,
+    pub shininess: Option<f32>,
+    pub diffuse_map: Option<PathBuf>,
+}
+
+/// Part of a model drawn with a single material
+pub(crate) struct ParsedSubMesh {
+    pub indices: Vec<u32>,
+    pub material: ParsedMaterial,
+}
+
+/// Vertices and normals of a parsed model, shared by all of its submeshes
+pub(crate) struct ParsedModel {
+    pub vertices: Vec<Vertex>,
+    pub normals: Vec<Normal>,
+    pub submeshes: Vec<ParsedSubMesh>,
+    /// Bounds of the vertices, after the import options were applied
+    pub bounds: Aabb,
+}
 
-pub(crate) fn parse_model(path: &str) -> (Vec<Vertex>, Vec<u32>, Vec<Normal>) {
-    let file = BufReader::new(fs::File::open(path).unwrap());
+pub(crate) fn parse_model(path: &str, options: &ImportOptions) -> Result<ParsedModel, ModelError> {
+    let file = BufReader::new(fs::File::open(path)?);
     println!("Loading model: {}", path);
-    let object: Obj<TexturedVertex, u16> = load_obj(file).expect("Failed to load model");
+    let raw = parse_obj(file)?;
+    let directory = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    let materials = parse_materials(&raw, directory);
 
-    let vertices: Vec<Vertex> = object.vertices.iter()
-        .map(|v| {
-            Vertex {
-                position: [v.position[0] / 200., v.position[1] / 200., v.position[2] / 200.],
-                tex_coords: [v.texture[0], v.texture[1]],
-            }
-        })
-        .collect();
+    let mut vertices = Vec::new();
+    let mut normals = Vec::new();
+    // Each distinct (position, texture, normal) triple becomes one vertex
+    let mut cache: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+
+    // Submeshes are kept in the order they first appear in the file
+    let mut meshes: Vec<(&String, &obj::raw::object::Group)> = raw.meshes.iter().collect();
+    meshes.sort_by_key(|(_, group)| group.polygons.first().map(|range| range.start));
+
+    let mut submeshes = Vec::with_capacity(meshes.len());
+    for (name, group) in meshes {
+        let mut indices = Vec::new();
+        for range in &group.polygons {
+            for polygon in &raw.polygons[range.start..range.end] {
+                let corners: Vec<(usize, Option<usize>, Option<usize>)> = match polygon {
+                    Polygon::P(p) => p.iter().map(|&p| (p, None, None)).collect(),
+                    Polygon::PT(pt) => pt.iter().map(|&(p, t)| (p, Some(t), None)).collect(),
+                    Polygon::PN(pn) => pn.iter().map(|&(p, n)| (p, None, Some(n))).collect(),
+                    Polygon::PTN(ptn) => ptn.iter().map(|&(p, t, n)| (p, Some(t), Some(n))).collect(),
+                };
 
-    let normals: Vec<Normal> = object.vertices.iter()
-        .map(|v| {
-            Normal {
-                normal: [v.normal[0], v.normal[1], v.normal[2]],
+                let mut polygon_indices = Vec::with_capacity(corners.len());
+                for corner in corners {
+                    let index = match cache.get(&corner) {
+                        Some(&index) => index,
+                        None => {
+                            let index = u32::try_from(vertices.len()).map_err(|_| ModelError::IndexOverflow)?;
+                            let (p, t, n) = corner;
+                            let position = raw.positions[p];
+                            let tex_coords = t.map_or((0., 0., 0.), |t| raw.tex_coords[t]);
+                            let normal = n.map_or((0., 0., 0.), |n| raw.normals[n]);
+                            let position = to_y_up([position.0, position.1, position.2], options.up_axis);
+                            vertices.push(Vertex {
+                                position: position.map(|c| c * options.unit_scale),
+                                tex_coords: [tex_coords.0, tex_coords.1],
+                            });
+                            normals.push(Normal {
+                                normal: to_y_up([normal.0, normal.1, normal.2], options.up_axis),
+                            });
+                            cache.insert(corner, index);
+                            index
+                        }
+                    };
+                    polygon_indices.push(index);
+                }
+
+                // Polygons with more than three corners are split in a triangle fan
+                for i in 1..polygon_indices.len().saturating_sub(1) {
+                    indices.extend([polygon_indices[0], polygon_indices[i], polygon_indices[i + 1]]);
+                }
             }
-        })
-        .collect();
+        }
+
+        if indices.is_empty() {
+            continue;
+        }
 
-    let indices: Vec<u32> = object.indices.iter()
-        .map(|i| {
-            *i as u32
-        })
-        .collect();
+        let material = materials.get(name.as_str())
+            .map(|material| ParsedMaterial {
+                diffuse: material.diffuse.as_ref().and_then(rgb),
+                specular: material.specular.as_ref().and_then(rgb),
+                shininess: material.specular_exponent,
+                diffuse_map: material.diffuse_map.as_ref().map(|map| directory.join(&map.file)),
+            })
+            .unwrap_or(ParsedMaterial {
+                diffuse: None,
+                specular: None,
+                shininess: None,
+                diffuse_map: None,
+            });
+
+        submeshes.push(ParsedSubMesh { indices, material });
+    }
+
+    let mut bounds = Aabb::from_points(vertices.iter().map(|v| v.position)).unwrap_or_default();
+    if options.normalize {
+        let center = bounds.center();
+        let largest = bounds.size().into_iter().fold(0., f32::max);
+        let factor = if largest > 0. { 1. / largest } else { 1. };
+        for vertex in &mut vertices {
+            vertex.position = [0, 1, 2].map(|i| (vertex.position[i] - center[i]) * factor);
+        }
+        bounds = Aabb {
+            min: [0, 1, 2].map(|i| (bounds.min[i] - center[i]) * factor),
+            max: [0, 1, 2].map(|i| (bounds.max[i] - center[i]) * factor),
+        };
+    }
 
     println!("Model loaded: {}", path);
-    (vertices, indices, normals)
-}
\ No newline at end of file
+    Ok(ParsedModel { vertices, normals, submeshes, bounds })
+}
+
+/// Rotates a vector from the up axis of the file to Y up
+fn to_y_up(vector: [f32; 3], up_axis: UpAxis) -> [f32; 3] {
+    match up_axis {
+        UpAxis::Y => vector,
+        UpAxis::Z => [vector[0], vector[2], -vector[1]],
+    }
+}
+
+/// Reads every material library referenced by the model.
+///
+/// Missing or invalid libraries are reported and skipped, leaving their materials to the defaults.
+fn parse_materials(raw: &RawObj, directory: &Path) -> HashMap<String, obj::raw::material::Material> {
+    let mut materials = HashMap::new();
+    for library in &raw.material_libraries {
+        let path = directory.join(library);
+        let parsed = match fs::File::open(&path) {
+            Ok(file) => parse_mtl(BufReader::new(file)).map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        match parsed {
+            Ok(mtl) => materials.extend(mtl.materials),
+            Err(e) => eprintln!("Could not load material library {}: {e}", path.display()),
+        }
+    }
+    materials
+}
+
+fn rgb(color: &MtlColor) -> Option<[f32; 3]> {
+    match *color {
+        MtlColor::Rgb(r, g, b) => Some([r, g, b]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+
+    use super::*;
+
+    /// Writes `contents` to a file of the temporary directory, unique to this process, and returns its path
+    fn temp_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("ogl_house_scene_{}_{name}", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Path of a file of `tests/fixtures`
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    #[test]
+    fn indices_past_u16() {
+        // Triangles that share no corner, each corner being a vertex of its own
+        const TRIANGLES: usize = 23_000;
+        let mut obj = String::new();
+        for i in 0..TRIANGLES * 3 {
+            writeln!(obj, "v {i} 0 0\nvt 0 {i}\nvn 0 0 1").unwrap();
+        }
+        for triangle in 0..TRIANGLES {
+            let [a, b, c] = [1, 2, 3].map(|corner| triangle * 3 + corner);
+            writeln!(obj, "f {a}/{a}/1 {b}/{b}/1 {c}/{c}/1").unwrap();
+        }
+        let path = temp_file("large.obj", &obj);
+
+        let model = parse_model(path.to_str().unwrap(), &ImportOptions::default());
+        fs::remove_file(&path).ok();
+        let model = model.unwrap();
+
+        assert_eq!(model.vertices.len(), TRIANGLES * 3);
+        let indices = &model.submeshes[0].indices;
+        assert!(indices.iter().any(|&index| index > u32::from(u16::MAX)));
+        for (i, &index) in indices.iter().enumerate() {
+            assert_eq!(index as usize, i);
+            assert_eq!(model.vertices[i].position[0], i as f32);
+            assert_eq!(model.vertices[i].tex_coords[1], i as f32);
+        }
+    }
+
+    #[test]
+    fn submeshes_in_file_order() {
+        let model = parse_model(&fixture("two_materials.obj"), &ImportOptions::default()).unwrap();
+
+        assert_eq!(model.submeshes.len(), 2);
+        // The quad is split in two triangles, the triangle shares none of its corners, their normals differ
+        assert_eq!(model.submeshes[0].indices, [0, 1, 2, 0, 2, 3]);
+        assert_eq!(model.submeshes[1].indices, [4, 5, 6]);
+        assert_eq!(model.vertices.len(), 7);
+        assert_eq!(model.normals[0].normal, [0., 0., 1.]);
+        assert_eq!(model.normals[4].normal, [0., 1., 0.]);
+        assert_eq!(model.vertices[6].tex_coords, [0., 1.]);
+    }
+
+    #[test]
+    fn materials_by_name() {
+        let model = parse_model(&fixture("two_materials.obj"), &ImportOptions::default()).unwrap();
+
+        let red = &model.submeshes[0].material;
+        assert_eq!(red.diffuse, Some([1., 0., 0.]));
+        assert_eq!(red.specular, Some([0.5, 0.5, 0.5]));
+        assert_eq!(red.shininess, Some(32.));
+        assert_eq!(red.diffuse_map, None);
+
+        let textured = &model.submeshes[1].material;
+        assert_eq!(textured.diffuse, Some([0.8, 0.8, 0.8]));
+        assert_eq!(textured.specular, None);
+        assert_eq!(textured.shininess, None);
+        // Relative to the directory of the model, not the working directory
+        let expected = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/textures/checker.png");
+        assert_eq!(textured.diffuse_map, Some(expected));
+    }
+
+    #[test]
+    fn missing_library_leaves_defaults() {
+        let model = parse_model(&fixture("missing_library.obj"), &ImportOptions::default()).unwrap();
+
+        assert_eq!(model.submeshes.len(), 1);
+        assert_eq!(model.submeshes[0].indices, [0, 1, 2]);
+        let material = &model.submeshes[0].material;
+        assert_eq!(material.diffuse, None);
+        assert_eq!(material.specular, None);
+        assert_eq!(material.shininess, None);
+        assert_eq!(material.diffuse_map, None);
+    }
+
+    #[test]
+    fn bounds_of_the_file() {
+        let model = parse_model(&fixture("two_materials.obj"), &ImportOptions::default()).unwrap();
+        assert_eq!(model.bounds, Aabb { min: [0., 0., 0.], max: [2., 1., 4.] });
+    }
+
+    #[test]
+    fn unit_scale() {
+        let options = ImportOptions { unit_scale: 0.5, ..ImportOptions::default() };
+        let model = parse_model(&fixture("two_materials.obj"), &options).unwrap();
+
+        assert_eq!(model.bounds, Aabb { min: [0., 0., 0.], max: [1., 0.5, 2.] });
+        assert_eq!(model.vertices[2].position, [1., 0.5, 0.]);
+        // Directions are left alone
+        assert_eq!(model.normals[0].normal, [0., 0., 1.]);
+    }
+
+    #[test]
+    fn z_up_to_y_up() {
+        let options = ImportOptions { up_axis: UpAxis::Z, ..ImportOptions::default() };
+        let model = parse_model(&fixture("two_materials.obj"), &options).unwrap();
+
+        // Z of the file goes up, Y of the file goes away from the viewer
+        assert_eq!(model.bounds, Aabb { min: [0., 0., -1.], max: [2., 4., 0.] });
+        assert_eq!(model.vertices[2].position, [2., 0., -1.]);
+        assert_eq!(model.vertices[6].position, [0., 4., 0.]);
+        assert_eq!(model.normals[0].normal, [0., 1., 0.]);
+        assert_eq!(model.normals[4].normal, [0., 0., -1.]);
+    }
+
+    #[test]
+    fn normalize_centres_and_fits() {
+        let options = ImportOptions { normalize: true, ..ImportOptions::default() };
+        let model = parse_model(&fixture("two_materials.obj"), &options).unwrap();
+
+        // Centred on (1, 0.5, 2), the largest side of 4 brought down to 1
+        let expected = Aabb { min: [-0.25, -0.125, -0.5], max: [0.25, 0.125, 0.5] };
+        assert_eq!(model.bounds, expected);
+        assert_eq!(model.vertices[0].position, [-0.25, -0.125, -0.5]);
+        assert_eq!(model.vertices[6].position, [-0.25, -0.125, 0.5]);
+        assert_eq!(Aabb::from_points(model.vertices.iter().map(|v| v.position)), Some(expected));
+
+        // The unit scale makes no difference once normalized
+        let scaled = ImportOptions { unit_scale: 10.0, ..options };
+        assert_eq!(parse_model(&fixture("two_materials.obj"), &scaled).unwrap().bounds, expected);
+    }
+}
@@ -0,0 +1,197 @@
+//! Programs shared by all models and the passes drawing a scene with them
+
+use glium::{DrawParameters, Program, ProgramCreationError, Surface};
+use glium::backend::Facade;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::{DepthFormat, DepthTexture2d, MipmapsOption, TextureCreationError};
+use glium::uniforms::Uniforms;
+
+use crate::assets::transform::Transform;
+use crate::camera::Camera;
+use crate::model::Model;
+use crate::model::light::{Light, LightUniforms, MAX_LIGHTS};
+use crate::model::shadow::{light_space_matrix, ShadowSettings, ShadowUniforms};
+use crate::scene::{Scene, SceneObject};
+
+const VERT_SHADER: &str = include_str!("../shaders/shader.vert");
+const FRAG_SHADER: &str = include_str!("../shaders/shader.frag");
+const SHADOW_VERT_SHADER: &str = include_str!("../shaders/shadow.vert");
+const SHADOW_FRAG_SHADER: &str = include_str!("../shaders/shadow.frag");
+const SKYBOX_VERT_SHADER: &str = include_str!("../shaders/skybox.vert");
+const SKYBOX_FRAG_SHADER: &str = include_str!("../shaders/skybox.frag");
+
+/// Holds the compiled programs and the uniforms shared by every model drawn in a frame.
+///
+/// Each display or headless context needs its own renderer, since programs can't be shared between contexts.
+pub struct Renderer {
+    program: Program,
+    shadow_program: Program,
+    skybox_program: Program,
+    /// Created by `prepare_shadows`, once shadows are enabled
+    shadow_map: Option<DepthTexture2d>,
+    /// Used to draw every model of the scene
+    draw_params: DrawParameters<'static>,
+    /// Color of the light reaching every surface, whatever its orientation
+    pub ambient: [f32; 3],
+    /// Lights of the scene, only the first `MAX_LIGHTS` are used
+    pub lights: Vec<Light>,
+    /// Rotation of the directional lights around the x axis, in radians
+    pub light_rotation: f32,
+    /// Shadows cast by the first directional light
+    pub shadows: ShadowSettings,
+}
+
+impl Renderer {
+    /// Compiles the programs for the given context
+    pub fn new<F: Facade>(display: &F) -> Result<Renderer, ProgramCreationError> {
+        Ok(Renderer {
+            program: Program::from_source(display, VERT_SHADER, FRAG_SHADER, None)?,
+            shadow_program: Program::from_source(display, SHADOW_VERT_SHADER, SHADOW_FRAG_SHADER, None)?,
+            skybox_program: Program::from_source(display, SKYBOX_VERT_SHADER, SKYBOX_FRAG_SHADER, None)?,
+            shadow_map: None,
+            draw_params: DrawParameters {
+                depth: glium::Depth {
+                    test: glium::DepthTest::IfLess,
+                    write: true,
+                    range: (0.0, 1.0),
+                    ..Default::default()
+                },
+                backface_culling: glium::draw_parameters::BackfaceCullingMode::CullingDisabled,
+                ..Default::default()
+            },
+            ambient: [1.0, 1.0, 1.0],
+            lights: vec![Light::default()],
+            light_rotation: 0.0,
+            shadows: ShadowSettings::default(),
+        })
+    }
+
+    /// Program used to draw lit and textured models
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// Program used to draw models into the shadow map
+    pub fn shadow_program(&self) -> &Program {
+        &self.shadow_program
+    }
+
+    /// Program used to draw the skybox
+    pub fn skybox_program(&self) -> &Program {
+        &self.skybox_program
+    }
+
+    /// Index of the light casting shadows and the matrix projecting world positions onto its shadow map,
+    /// or `None` when shadows are disabled or there is no directional light
+    pub fn shadow_caster(&self) -> Option<(usize, [[f32; 4]; 4])> {
+        if !self.shadows.enabled {
+            return None;
+        }
+        self.lights.iter()
+            .take(MAX_LIGHTS)
+            .enumerate()
+            .find_map(|(i, light)| light.rotated_direction(self.light_rotation).map(|direction| (i, light_space_matrix(direction))))
+    }
+
+    /// Creates the shadow map, or recreates it when the resolution changed.
+    ///
+    /// If the new shadow map can't be created, the previous one is kept and the settings are reverted,
+    /// or shadows are disabled if there is none.
+    pub fn prepare_shadows<F: Facade>(&mut self, display: &F) -> Result<(), TextureCreationError> {
+        let resolution = self.shadows.resolution;
+        if !self.shadows.enabled || self.shadow_map.as_ref().is_some_and(|map| map.width() == resolution) {
+            return Ok(());
+        }
+
+        match DepthTexture2d::empty_with_format(display, DepthFormat::I24, MipmapsOption::NoMipmap, resolution, resolution) {
+            Ok(map) => {
+                self.shadow_map = Some(map);
+                Ok(())
+            }
+            Err(e) => {
+                match &self.shadow_map {
+                    Some(map) => self.shadows.resolution = map.width(),
+                    None => self.shadows.enabled = false,
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Clears the shadow map and returns a target to draw the shadow casters into,
+    /// or `None` when no shadows should be drawn this frame
+    pub fn shadow_target<F: Facade>(&self, display: &F) -> Option<SimpleFrameBuffer<'_>> {
+        self.shadow_caster()?;
+        let map = self.shadow_map.as_ref()?;
+        let mut target = SimpleFrameBuffer::depth_only(display, map)
+            .map_err(|e| eprintln!("Could not draw shadows: {e:?}"))
+            .ok()?;
+        target.clear_depth(1.0);
+        Some(target)
+    }
+
+    /// Adds the uniforms describing the lights, in the view space of `view`, and the shadow map to the uniforms
+    /// of a draw call
+    pub fn lit<U: Uniforms>(&self, view: [[f32; 4]; 4], base: U) -> ShadowUniforms<'_, LightUniforms<'_, U>> {
+        let shadow = self.shadow_map.as_ref()
+            .zip(self.shadow_caster())
+            .map(|(map, (light, light_space))| (map, light_space, light));
+
+        ShadowUniforms {
+            base: LightUniforms {
+                base,
+                ambient: self.ambient,
+                lights: &self.lights,
+                rotation: self.light_rotation,
+                view,
+            },
+            shadow,
+            bias: self.shadows.bias,
+        }
+    }
+
+    /// Clears the target and draws the scene as seen from the camera: the shadow map, every object and the skybox.
+    ///
+    /// `local` gives the transform of an object relative to its parent, as in `Scene::transforms`.
+    pub fn draw_scene<'a, F: Facade, S: Surface>(&self, display: &F, target: &mut S, scene: &'a Scene, camera: &Camera, local: impl Fn(&'a SceneObject) -> Transform<'a>) {
+        target.clear_color_and_depth((0., 0., 1., 1.), 1.);
+
+        let dimensions = target.get_dimensions();
+
+        let transforms = scene.transforms(|object| Transform {
+            view: camera.view(),
+            frame_dimensions: Some(dimensions),
+            zfar: camera.zfar,
+            znear: camera.znear,
+            fov: camera.fov,
+            ..local(object)
+        });
+
+        // Depth pass from the light, read back by the main pass to find occluded fragments
+        if let Some(mut shadow_target) = self.shadow_target(display) {
+            for (object, transform) in scene.objects.iter().zip(&transforms) {
+                if let Some(model) = object.model.as_ref().filter(|_| object.cast_shadows) {
+                    model.draw_shadow(&mut shadow_target, self, transform);
+                }
+            }
+        }
+
+        for (object, transform) in scene.objects.iter().zip(&transforms) {
+            if let Some(model) = &object.model {
+                model.draw(target, self, &self.draw_params, transform);
+            }
+        }
+
+        // Drawn last so that it only covers what no object was drawn over
+        if let Some(skybox) = &scene.skybox {
+            let transform = Transform {
+                view: camera.view(),
+                frame_dimensions: Some(dimensions),
+                znear: camera.znear,
+                fov: camera.fov,
+                ..Default::default()
+            };
+            skybox.draw(target, self, &transform);
+        }
+    }
+}
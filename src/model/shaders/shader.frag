@@ -1,24 +1,105 @@
 #version 330
 
-uniform vec3 light;
-uniform mat4 light_rotation;
+#define MAX_LIGHTS 8
+#define DIRECTIONAL 0
+#define POINT 1
+#define SPOT 2
+
+// Positions and directions are in view space, like the fragment
+struct Light {
+    int kind;
+    vec3 position;
+    // Direction the light travels in
+    vec3 direction;
+    vec3 color;
+    float intensity;
+    // Constant, linear and quadratic factors
+    vec3 attenuation;
+    // Cosines of the inner and outer angles of spot lights
+    float inner_cutoff;
+    float outer_cutoff;
+};
+
+uniform Light lights[MAX_LIGHTS];
+uniform int light_count;
+uniform vec3 ambient_light;
 uniform sampler2D tex;
+uniform vec3 material_diffuse;
+uniform vec3 material_specular;
+uniform float material_shininess;
+// Depth of the closest surfaces seen from the light casting shadows
+uniform sampler2D shadow_map;
+// Index of the light casting shadows, -1 when shadows are disabled
+uniform int shadow_light;
+uniform float shadow_bias;
 
 in vec2 v_tex_coords;
 in vec3 v_normal;
 in vec3 v_position;
+in vec4 v_light_space_position;
 out vec4 color;
 
-vec3 ambient_color = vec3(texture(tex, v_tex_coords)) * 0.45;
-vec3 diffuse_color = ambient_color * 1.55;
-vec3 specular_color = ambient_color * 4.0;
+// Fraction of the light reaching the fragment, averaged over the 3x3 texels around it
+float shadow_factor(vec3 normal, vec3 light_dir) {
+    vec3 coords = v_light_space_position.xyz / v_light_space_position.w * 0.5 + 0.5;
+    // Outside of the shadow map nothing is known to block the light
+    if (any(lessThan(coords, vec3(0.0))) || any(greaterThan(coords, vec3(1.0)))) {
+        return 1.0;
+    }
+
+    // Surfaces facing away from the light need a larger offset
+    float bias = shadow_bias * (1.0 + 3.0 * (1.0 - max(dot(normal, light_dir), 0.0)));
+    vec2 texel = 1.0 / vec2(textureSize(shadow_map, 0));
+
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            float closest = texture(shadow_map, coords.xy + vec2(x, y) * texel).r;
+            lit += coords.z - bias > closest ? 0.0 : 1.0;
+        }
+    }
+    return lit / 9.0;
+}
 
 void main() {
-    vec3 ulight = vec3(light_rotation * vec4(light, 1.0));
-    float diffuse = max(dot(normalize(v_normal), normalize(ulight)), 0.0);
+    vec3 ambient_color = vec3(texture(tex, v_tex_coords)) * material_diffuse * 0.45;
+    vec3 diffuse_color = ambient_color * 1.55;
+    vec3 specular_color = ambient_color * 4.0 * material_specular;
+
+    vec3 normal = normalize(v_normal);
+    // The camera sits at the origin of view space
     vec3 camera_dir = normalize(-v_position);
-    vec3 half_direction = normalize(normalize(ulight) + camera_dir);
-    float specular = pow(max(dot(half_direction, normalize(v_normal)), 0.0), 16.0);
-    color = vec4(ambient_color + diffuse * diffuse_color + specular * specular_color, 1.0);
-}
 
+    vec3 diffuse = vec3(0.0);
+    vec3 specular = vec3(0.0);
+    for (int i = 0; i < light_count; i++) {
+        Light light = lights[i];
+        vec3 light_dir;
+        float attenuation = 1.0;
+
+        if (light.kind == DIRECTIONAL) {
+            light_dir = normalize(-light.direction);
+        } else {
+            vec3 to_light = light.position - v_position;
+            float distance = length(to_light);
+            light_dir = to_light / distance;
+            attenuation = 1.0 / (light.attenuation.x + light.attenuation.y * distance + light.attenuation.z * distance * distance);
+
+            if (light.kind == SPOT) {
+                float theta = dot(light_dir, normalize(-light.direction));
+                attenuation *= clamp((theta - light.outer_cutoff) / (light.inner_cutoff - light.outer_cutoff), 0.0, 1.0);
+            }
+        }
+
+        if (i == shadow_light) {
+            attenuation *= shadow_factor(normal, light_dir);
+        }
+
+        vec3 radiance = light.color * light.intensity * attenuation;
+        vec3 half_direction = normalize(light_dir + camera_dir);
+        diffuse += max(dot(normal, light_dir), 0.0) * radiance;
+        specular += pow(max(dot(half_direction, normal), 0.0), material_shininess) * radiance;
+    }
+
+    color = vec4(ambient_color * ambient_light + diffuse * diffuse_color + specular * specular_color, 1.0);
+}
@@ -5,28 +5,28 @@ in vec2 tex_coords;
 
 out vec3 v_normal, v_position;
 out vec2 v_tex_coords;
+out vec4 v_light_space_position;
 
-uniform mat4 translation, rotation, scale, self_rotation, view, perspective;
+// Matrices computed once per object on the CPU
+uniform mat4 model, view, projection;
+// Turns normals into world space, the inverse transpose of the model matrix
+uniform mat3 normal_matrix;
+// Projects world positions onto the shadow map
+uniform mat4 light_space;
 
 void main() {
     v_tex_coords = tex_coords;
 
-    // Operations occur from right to left
-    mat4 matrix =
-    perspective *
-    view *
-    rotation *
-    translation *
-    scale *
-    self_rotation;
+    vec4 world_position = model * vec4(position, 1.0);
+    vec4 view_position = view * world_position;
 
-    gl_Position =
-    matrix *
-    vec4(position, 1.0);
+    gl_Position = projection * view_position;
 
-    v_normal =
-    inverse(transpose(mat3(matrix))) *
-    normal;
+    // Lighting happens in view space, where the camera is at the origin.
+    // The view matrix only rotates and translates, so it turns normals like any other direction
+    v_normal = mat3(view) * normal_matrix * normal;
 
-    v_position = gl_Position.xyz / gl_Position.w;
+    v_position = view_position.xyz / view_position.w;
+
+    v_light_space_position = light_space * world_position;
 }
@@ -0,0 +1,5 @@
+#version 330
+
+// Only the depth of the fragments is written to the shadow map
+void main() {
+}
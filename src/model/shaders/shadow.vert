@@ -0,0 +1,13 @@
+#version 330
+
+in vec3 position;
+
+// Same model matrix as the lit shader, so that shadows line up with the models
+uniform mat4 model, light_space;
+
+void main() {
+    gl_Position =
+    light_space *
+    model *
+    vec4(position, 1.0);
+}
@@ -0,0 +1,10 @@
+#version 330
+
+uniform samplerCube skybox;
+
+in vec3 v_direction;
+out vec4 color;
+
+void main() {
+    color = texture(skybox, v_direction);
+}
@@ -0,0 +1,20 @@
+#version 330
+
+in vec3 position;
+
+out vec3 v_direction;
+
+uniform mat4 view, projection;
+
+void main() {
+    v_direction = position;
+
+    // Only the rotation of the camera is kept, so that the cube stays centred on it
+    vec4 clip_position =
+    projection *
+    mat4(mat3(view)) *
+    vec4(position, 1.0);
+
+    // Setting z to w puts every fragment on the far plane
+    gl_Position = clip_position.xyww;
+}
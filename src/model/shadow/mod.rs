@@ -0,0 +1,171 @@
+//! Shadow mapping for the first directional light of the scene
+
+use glium::{Depth, DepthTest, DrawParameters};
+use glium::texture::DepthTexture2d;
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerBehavior, SamplerWrapFunction, UniformValue, Uniforms};
+
+use crate::assets::matrices::{multiply_matrices, orthographic_matrix, view_matrix};
+use crate::math::Vec3;
+
+/// Half the width of the square around the origin covered by the shadow map, in world units
+const SHADOW_EXTENT: f32 = 3.0;
+
+/// Distance from the origin, against the direction of the light, from which the shadow map is rendered
+const SHADOW_DISTANCE: f32 = 5.0;
+
+/// Settings of the shadows cast by the first directional light, which can be changed while running
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShadowSettings {
+    /// Whether the first directional light casts shadows
+    pub enabled: bool,
+    /// Width and height of the shadow map, in texels
+    pub resolution: u32,
+    /// Depth offset, in shadow map units, that keeps surfaces from shadowing themselves
+    pub bias: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        ShadowSettings {
+            enabled: true,
+            resolution: 2048,
+            bias: 0.0005,
+        }
+    }
+}
+
+/// Matrix projecting world positions onto the shadow map of a directional light travelling in `direction`
+pub fn light_space_matrix(direction: [f32; 3]) -> [[f32; 4]; 4] {
+    let direction = Vec3::from(direction).normalize_or(-Vec3::Y).to_array();
+    let up = if direction[1].abs() > 0.99 { [0.0, 0.0, 1.0] } else { [0.0, 1.0, 0.0] };
+    let position = direction.map(|c| -c * SHADOW_DISTANCE);
+
+    multiply_matrices(
+        &orthographic_matrix(SHADOW_EXTENT, SHADOW_EXTENT, 0.0, 2.0 * SHADOW_DISTANCE),
+        &view_matrix(&position, &direction, &up),
+    )
+}
+
+/// Draw parameters of the depth-only pass rendering the shadow map
+pub fn depth_parameters() -> DrawParameters<'static> {
+    DrawParameters {
+        depth: Depth {
+            test: DepthTest::IfLess,
+            write: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// Adds the shadow map to the uniforms of a draw call.
+///
+/// When `shadow` is `None` the shader is told that no light casts shadows.
+pub struct ShadowUniforms<'a, U: Uniforms> {
+    /// Uniforms of the draw call the shadow map is added to
+    pub base: U,
+    /// Shadow map, the matrix projecting world positions onto it and the index of the light casting the shadows
+    pub shadow: Option<(&'a DepthTexture2d, [[f32; 4]; 4], usize)>,
+    /// Depth offset, in shadow map units, that keeps surfaces from shadowing themselves
+    pub bias: f32,
+}
+
+impl<U: Uniforms> Uniforms for ShadowUniforms<'_, U> {
+    fn visit_values<'b, F: FnMut(&str, UniformValue<'b>)>(&'b self, mut output: F) {
+        self.base.visit_values(&mut output);
+
+        match self.shadow {
+            Some((map, light_space, light)) => {
+                let sampler = SamplerBehavior {
+                    wrap_function: (SamplerWrapFunction::Clamp, SamplerWrapFunction::Clamp, SamplerWrapFunction::Clamp),
+                    minify_filter: MinifySamplerFilter::Nearest,
+                    magnify_filter: MagnifySamplerFilter::Nearest,
+                    ..Default::default()
+                };
+                output("shadow_map", UniformValue::DepthTexture2d(map, Some(sampler)));
+                output("light_space", UniformValue::Mat4(light_space));
+                output("shadow_light", UniformValue::SignedInt(light as i32));
+                output("shadow_bias", UniformValue::Float(self.bias));
+            }
+            None => output("shadow_light", UniformValue::SignedInt(-1)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Normalized device coordinates of a world position
+    fn project(matrix: &[[f32; 4]; 4], point: [f32; 3]) -> [f32; 3] {
+        let clip: [f32; 4] = [0, 1, 2, 3].map(|row| (0..3).map(|column| matrix[column][row] * point[column]).sum::<f32>() + matrix[3][row]);
+        [clip[0] / clip[3], clip[1] / clip[3], clip[2] / clip[3]]
+    }
+
+    fn assert_inside(matrix: &[[f32; 4]; 4], point: [f32; 3]) {
+        let ndc = project(matrix, point);
+        assert!(ndc.iter().all(|c| c.abs() <= 1.0 + 1e-5), "{point:?} projected outside the shadow map, to {ndc:?}");
+    }
+
+    /// Corners of the cube of the given half width around the origin, along with its center
+    fn cube(half_width: f32) -> Vec<[f32; 3]> {
+        let mut points = vec![[0.0; 3]];
+        for x in [-1.0, 1.0] {
+            for y in [-1.0, 1.0] {
+                for z in [-1.0, 1.0] {
+                    points.push([x * half_width, y * half_width, z * half_width]);
+                }
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn axis_aligned_light_covers_the_box() {
+        for direction in [[0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]] {
+            let matrix = light_space_matrix(direction);
+            for point in cube(SHADOW_EXTENT) {
+                assert_inside(&matrix, point);
+            }
+        }
+    }
+
+    /// Points on the surface of the ball as wide as the box, which the map covers whatever the direction
+    fn ball() -> Vec<[f32; 3]> {
+        let mut points = cube(SHADOW_EXTENT / 3.0f32.sqrt());
+        for axis in 0..3 {
+            for sign in [-1.0, 1.0] {
+                let mut point = [0.0; 3];
+                point[axis] = sign * SHADOW_EXTENT;
+                points.push(point);
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn oblique_light_covers_the_ball() {
+        // Corners of the box stick out of the sides of the map, the ball it holds never does
+        for direction in [[-1.0, -1.0, -1.0], [0.3, -0.8, 0.5], [1.0, -0.2, 0.0]] {
+            let matrix = light_space_matrix(direction);
+            for point in ball() {
+                assert_inside(&matrix, point);
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate_directions() {
+        // Parallel to the up vector of the view, or no direction at all
+        for direction in [[0.0, -1.0, 0.0], [0.0, 1.0, 0.0], [0.0, -2.0, 0.001], [0.0, 0.0, 0.0]] {
+            let matrix = light_space_matrix(direction);
+            assert!(matrix.iter().flatten().all(|c| c.is_finite()), "{direction:?} gave {matrix:?}");
+            for point in ball() {
+                assert_inside(&matrix, point);
+            }
+        }
+
+        // No direction lights the scene from above
+        assert_eq!(light_space_matrix([0.0; 3]), light_space_matrix([0.0, -1.0, 0.0]));
+    }
+}
@@ -0,0 +1,204 @@
+//! Cube map drawn behind the scene
+
+use std::fmt;
+use std::path::Path;
+
+use glium::{BlitTarget, Depth, DepthTest, DrawParameters, IndexBuffer, Rect, Surface, VertexBuffer};
+use glium::backend::Facade;
+use glium::framebuffer::{SimpleFrameBuffer, ValidationError};
+use glium::index::PrimitiveType;
+use glium::texture::{CubeLayer, MipmapsOption, RawImage2d, SrgbCubemap, SrgbTexture2d, TextureCreationError};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerWrapFunction};
+
+use crate::assets::{
+    load_tex::{load_image, TextureError},
+    transform::Transform,
+    vertex::Vertex,
+};
+use crate::model::renderer::Renderer;
+
+/// Faces of the cubemap, in the order `from_faces` expects them
+const LAYERS: [CubeLayer; 6] = [
+    CubeLayer::PositiveX,
+    CubeLayer::NegativeX,
+    CubeLayer::PositiveY,
+    CubeLayer::NegativeY,
+    CubeLayer::PositiveZ,
+    CubeLayer::NegativeZ,
+];
+
+/// Column and row, from the top left, of each face in a horizontal cross image, in the order of `LAYERS`
+const CROSS_CELLS: [(u32, u32); 6] = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)];
+
+/// Errors that can happen while building a skybox
+#[derive(Debug)]
+pub enum SkyboxError {
+    /// An image could not be loaded
+    Texture(TextureError),
+    /// The image of the given size can't be split into square faces of the same size
+    Layout(u32, u32),
+    /// A face could not be copied into the cubemap
+    Framebuffer(ValidationError),
+    /// The vertex buffer of the cube could not be created on the GPU
+    VertexBuffer(glium::vertex::BufferCreationError),
+    /// The index buffer of the cube could not be created on the GPU
+    IndexBuffer(glium::index::BufferCreationError),
+}
+
+impl fmt::Display for SkyboxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkyboxError::Texture(e) => write!(f, "{e}"),
+            SkyboxError::Layout(width, height) => write!(f, "an image of {width}x{height} pixels doesn't hold square faces of the same size"),
+            SkyboxError::Framebuffer(e) => write!(f, "could not copy face into cubemap: {e:?}"),
+            SkyboxError::VertexBuffer(e) => write!(f, "could not create vertex buffer: {e}"),
+            SkyboxError::IndexBuffer(e) => write!(f, "could not create index buffer: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for SkyboxError {}
+
+impl From<TextureError> for SkyboxError {
+    fn from(e: TextureError) -> Self {
+        SkyboxError::Texture(e)
+    }
+}
+
+impl From<TextureCreationError> for SkyboxError {
+    fn from(e: TextureCreationError) -> Self {
+        SkyboxError::Texture(TextureError::Creation(e))
+    }
+}
+
+impl From<ValidationError> for SkyboxError {
+    fn from(e: ValidationError) -> Self {
+        SkyboxError::Framebuffer(e)
+    }
+}
+
+impl From<glium::vertex::BufferCreationError> for SkyboxError {
+    fn from(e: glium::vertex::BufferCreationError) -> Self {
+        SkyboxError::VertexBuffer(e)
+    }
+}
+
+impl From<glium::index::BufferCreationError> for SkyboxError {
+    fn from(e: glium::index::BufferCreationError) -> Self {
+        SkyboxError::IndexBuffer(e)
+    }
+}
+
+/// Cube textured from the inside, drawn around the camera behind everything else
+pub struct Skybox {
+    cubemap: SrgbCubemap,
+    vertices: VertexBuffer<Vertex>,
+    indices: IndexBuffer<u16>,
+}
+
+impl Skybox {
+    /// Builds a skybox from a single image with the faces laid out as a horizontal cross.
+    ///
+    /// The image is 4 faces wide and 3 faces high, the middle row holding -x, +z, +x and -z,
+    /// with +y above and -y below +z.
+    pub fn from_cross<F: Facade, P: AsRef<Path>>(display: &F, path: P) -> Result<Skybox, SkyboxError> {
+        let image = load_image(path)?;
+        let (width, height) = image.dimensions();
+        let size = width / 4;
+        if size == 0 || width != size * 4 || height != size * 3 {
+            return Err(SkyboxError::Layout(width, height));
+        }
+
+        let cross = upload(display, image)?;
+        let source = SimpleFrameBuffer::new(display, &cross)?;
+        let cubemap = SrgbCubemap::empty_with_mipmaps(display, MipmapsOption::NoMipmap, size)?;
+
+        for (layer, (column, row)) in LAYERS.into_iter().zip(CROSS_CELLS) {
+            let face = SimpleFrameBuffer::new(display, cubemap.main_level().image(layer))?;
+            let rect = Rect { left: column * size, bottom: row * size, width: size, height: size };
+            source.blit_color(&rect, &face, &whole(size), MagnifySamplerFilter::Nearest);
+        }
+
+        Skybox::new(display, cubemap)
+    }
+
+    /// Builds a skybox from six square images of the same size, given in the order +x, -x, +y, -y, +z, -z
+    pub fn from_faces<F: Facade, P: AsRef<Path>>(display: &F, paths: &[P; 6]) -> Result<Skybox, SkyboxError> {
+        let images = paths.iter().map(load_image).collect::<Result<Vec<_>, _>>()?;
+        let size = images[0].width();
+        if let Some(image) = images.iter().find(|image| size == 0 || image.dimensions() != (size, size)) {
+            return Err(SkyboxError::Layout(image.width(), image.height()));
+        }
+
+        let cubemap = SrgbCubemap::empty_with_mipmaps(display, MipmapsOption::NoMipmap, size)?;
+        for (layer, image) in LAYERS.into_iter().zip(images) {
+            let source = upload(display, image)?;
+            let face = SimpleFrameBuffer::new(display, cubemap.main_level().image(layer))?;
+            SimpleFrameBuffer::new(display, &source)?.blit_color(&whole_rect(size), &face, &whole(size), MagnifySamplerFilter::Nearest);
+        }
+
+        Skybox::new(display, cubemap)
+    }
+
+    /// Creates the cube the cubemap is drawn on
+    fn new<F: Facade>(display: &F, cubemap: SrgbCubemap) -> Result<Skybox, SkyboxError> {
+        let vertices: Vec<Vertex> = (0..8)
+            .map(|i| Vertex {
+                position: [0, 1, 2].map(|axis| if i & (1 << axis) == 0 { -1.0 } else { 1.0 }),
+                tex_coords: [0.0, 0.0],
+            })
+            .collect();
+        let indices: [u16; 36] = [
+            0, 2, 6, 6, 4, 0, // -x
+            1, 5, 7, 7, 3, 1, // +x
+            0, 4, 5, 5, 1, 0, // -y
+            2, 3, 7, 7, 6, 2, // +y
+            0, 1, 3, 3, 2, 0, // -z
+            4, 6, 7, 7, 5, 4, // +z
+        ];
+
+        Ok(Skybox {
+            cubemap,
+            vertices: VertexBuffer::new(display, &vertices)?,
+            indices: IndexBuffer::new(display, PrimitiveType::TrianglesList, &indices)?,
+        })
+    }
+
+    /// Draws the skybox around the camera of `transform`, only where nothing else was drawn.
+    ///
+    /// It must be drawn after the rest of the scene, as it is unlit, doesn't write depth and
+    /// lies on the far plane whatever `zfar` is.
+    pub fn draw<S: Surface>(&self, target: &mut S, renderer: &Renderer, transform: &Transform) {
+        let params = DrawParameters {
+            depth: Depth {
+                test: DepthTest::IfLessOrEqual,
+                write: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let uniforms = uniform! {
+            view: transform.get_view(),
+            projection: transform.get_projection(),
+            skybox: self.cubemap.sampled()
+                .minify_filter(MinifySamplerFilter::Linear)
+                .magnify_filter(MagnifySamplerFilter::Linear)
+                .wrap_function(SamplerWrapFunction::Clamp),
+        };
+        target.draw(&self.vertices, &self.indices, renderer.skybox_program(), &uniforms, &params).unwrap();
+    }
+}
+
+/// Creates a texture holding the image unflipped, as cubemap faces start from their top row
+fn upload<F: Facade>(display: &F, image: image::RgbaImage) -> Result<SrgbTexture2d, TextureCreationError> {
+    let dimensions = image.dimensions();
+    SrgbTexture2d::with_mipmaps(display, RawImage2d::from_raw_rgba(image.into_raw(), dimensions), MipmapsOption::NoMipmap)
+}
+
+fn whole_rect(size: u32) -> Rect {
+    Rect { left: 0, bottom: 0, width: size, height: size }
+}
+
+fn whole(size: u32) -> BlitTarget {
+    BlitTarget { left: 0, bottom: 0, width: size as i32, height: size as i32 }
+}
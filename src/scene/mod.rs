@@ -0,0 +1,653 @@
+//! Scenes described in RON files, and the objects they hold
+
+use std::{fmt, fs, io};
+
+use glium::backend::Facade;
+use glium::texture::SrgbTexture2d;
+use serde::Deserialize;
+
+use crate::animation::Animation;
+use crate::collision::CollisionWorld;
+use crate::math::Quat;
+use crate::assets::{
+    bounds::Aabb,
+    load_tex::{load_texture, placeholder_texture, TextureError},
+    transform::{euler_rotation, Transform},
+    vertex::{Normal, Vertex},
+};
+use crate::model::generic_model::GenericModel;
+use crate::model::{ImportOptions, ModelError};
+use crate::model::light::Light;
+use crate::model::skybox::{Skybox, SkyboxError};
+
+/// Scene loaded when no scene file is given on the command line
+pub const DEFAULT_SCENE: &str = "scenes/gas_station.ron";
+
+/// Size, in world units, of the cube shown in place of a model that failed to load
+const PLACEHOLDER_SIZE: f32 = 0.1;
+
+/// Errors that can happen while loading a scene file
+#[derive(Debug)]
+pub enum SceneError {
+    /// The scene file could not be read
+    Io(io::Error),
+    /// The scene file is not a valid scene description
+    Parse(ron::error::SpannedError),
+    /// A model could not be created, not even as a placeholder
+    Model(ModelError),
+    /// A texture could not be created, not even as a placeholder
+    Texture(TextureError),
+    /// The scene file is, through its bases, its own base
+    BaseCycle(String),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(e) => write!(f, "could not read scene file: {e}"),
+            SceneError::Parse(e) => write!(f, "invalid scene file: {e}"),
+            SceneError::Model(e) => write!(f, "{e}"),
+            SceneError::Texture(e) => write!(f, "{e}"),
+            SceneError::BaseCycle(path) => write!(f, "scene file {path} is its own base"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<io::Error> for SceneError {
+    fn from(e: io::Error) -> Self {
+        SceneError::Io(e)
+    }
+}
+
+impl From<ron::error::SpannedError> for SceneError {
+    fn from(e: ron::error::SpannedError) -> Self {
+        SceneError::Parse(e)
+    }
+}
+
+impl From<ModelError> for SceneError {
+    fn from(e: ModelError) -> Self {
+        SceneError::Model(e)
+    }
+}
+
+impl From<TextureError> for SceneError {
+    fn from(e: TextureError) -> Self {
+        SceneError::Texture(e)
+    }
+}
+
+/// Description of a scene, as written in a scene file
+#[derive(Deserialize)]
+pub struct SceneDescription {
+    /// Scene file whose objects come before the ones of this scene, and whose skybox, focus and bounds are used
+    /// when this scene has none. Its ambient light and lights are not, so that a variant of a scene only needs
+    /// to list them.
+    #[serde(default)]
+    pub base: Option<String>,
+    /// Objects of the scene, parents listed before their children
+    #[serde(default)]
+    pub objects: Vec<ObjectDescription>,
+    /// Color of the ambient light
+    #[serde(default = "default_ambient")]
+    pub ambient: [f32; 3],
+    /// Light sources, a single sun when there are none
+    #[serde(default = "default_lights")]
+    pub lights: Vec<Light>,
+    /// Images drawn behind every object
+    #[serde(default)]
+    pub skybox: Option<SkyboxDescription>,
+    /// Name of the object the orbit camera turns around, the origin when there is none
+    #[serde(default)]
+    pub focus: Option<String>,
+    /// Box the camera is kept within, the box holding every object when there is none
+    #[serde(default)]
+    pub bounds: Option<Aabb>,
+}
+
+impl SceneDescription {
+    /// Reads a scene file, along with the base it is built on
+    pub fn read(path: &str) -> Result<SceneDescription, SceneError> {
+        read_description(path, &mut Vec::new())
+    }
+}
+
+/// Reads a scene file and its bases, `chain` holding the files being read that it is the base of
+fn read_description(path: &str, chain: &mut Vec<String>) -> Result<SceneDescription, SceneError> {
+    if chain.iter().any(|read| read == path) {
+        return Err(SceneError::BaseCycle(path.to_string()));
+    }
+    let mut description: SceneDescription = ron::from_str(&fs::read_to_string(path)?)?;
+
+    if let Some(base_path) = description.base.take() {
+        chain.push(path.to_string());
+        let base = read_description(&base_path, chain)?;
+        chain.pop();
+
+        let mut objects = base.objects;
+        objects.append(&mut description.objects);
+        description.objects = objects;
+        description.skybox = description.skybox.or(base.skybox);
+        description.focus = description.focus.or(base.focus);
+        description.bounds = description.bounds.or(base.bounds);
+    }
+    Ok(description)
+}
+
+fn default_ambient() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_lights() -> Vec<Light> {
+    vec![Light::default()]
+}
+
+/// Description of a single object of the scene
+#[derive(Deserialize)]
+pub struct ObjectDescription {
+    /// Name the object is referred to by, as the focus of the scene or the parent of other objects
+    pub name: String,
+    /// Where the vertices of the object come from
+    pub mesh: MeshDescription,
+    /// Options used when loading an .obj mesh
+    #[serde(default)]
+    pub import: ImportOptions,
+    /// Path to the texture image, unused by empty objects
+    #[serde(default)]
+    pub texture: String,
+    /// Translate in [x, y, z]
+    #[serde(default)]
+    pub translation: [f32; 3],
+    /// Rotate in [x, y, z], see `euler_rotation`
+    #[serde(default)]
+    pub rotation: [f32; 3],
+    /// Rotate object around itself in [x, y, z], see `euler_rotation`
+    #[serde(default)]
+    pub rotate_self: [f32; 3],
+    /// Scale in s
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    /// Whether the object follows the spin controlled by the keyboard
+    #[serde(default)]
+    pub controllable: bool,
+    /// Keyframes overriding the transform above while the scene runs
+    #[serde(default)]
+    pub animation: Animation,
+    /// Whether the object is drawn into the shadow map
+    #[serde(default = "default_cast_shadows")]
+    pub cast_shadows: bool,
+    /// Whether the camera is stopped by the bounding box of the object
+    #[serde(default = "default_collides")]
+    pub collides: bool,
+    /// Objects placed relative to this one, following its translation and rotations but not its scale
+    #[serde(default)]
+    pub children: Vec<ObjectDescription>,
+}
+
+fn default_cast_shadows() -> bool {
+    true
+}
+
+fn default_collides() -> bool {
+    true
+}
+
+fn default_scale() -> f32 {
+    Transform::default().scale
+}
+
+/// Where the vertices of an object come from
+#[derive(Deserialize)]
+pub enum MeshDescription {
+    /// Path to a wavefront .obj file
+    Obj(String),
+    /// A flat quad given by its four corners, drawn as two triangles
+    Quad {
+        /// Corners of the quad, in order around its edge
+        corners: [[f32; 3]; 4],
+        /// Texture coordinates of each corner
+        tex_coords: [[f32; 2]; 4],
+        /// Normal shared by the whole quad
+        normal: [f32; 3],
+    },
+    /// Nothing is drawn, used as a pivot or to group other objects
+    Empty,
+}
+
+/// Where the faces of the skybox come from
+#[derive(Deserialize)]
+pub enum SkyboxDescription {
+    /// Path to a single image with the faces laid out as a horizontal cross
+    Cross(String),
+    /// Paths to one image per face
+    Faces {
+        /// Image of the face towards positive x
+        right: String,
+        /// Image of the face towards negative x
+        left: String,
+        /// Image of the face towards positive y
+        top: String,
+        /// Image of the face towards negative y
+        bottom: String,
+        /// Image of the face towards positive z
+        front: String,
+        /// Image of the face towards negative z
+        back: String,
+    },
+}
+
+impl SkyboxDescription {
+    /// Loads the images and builds the skybox
+    pub fn load<F: Facade>(&self, display: &F) -> Result<Skybox, SkyboxError> {
+        match self {
+            SkyboxDescription::Cross(path) => Skybox::from_cross(display, path),
+            SkyboxDescription::Faces { right, left, top, bottom, front, back } =>
+                Skybox::from_faces(display, &[right, left, top, bottom, front, back]),
+        }
+    }
+}
+
+/// An object of the scene, ready to be drawn
+pub struct SceneObject {
+    /// Name of the object, as given in the scene file
+    pub name: String,
+    /// `None` for empty objects
+    pub model: Option<GenericModel>,
+    /// Texture of the submeshes whose material has none
+    pub texture: Option<SrgbTexture2d>,
+    /// Index in `Scene::objects` of the object this one is placed relative to
+    pub parent: Option<usize>,
+    /// Translation relative to the parent, or the world
+    pub translation: [f32; 3],
+    /// Rotation around the origin of the parent
+    pub rotation: Quat,
+    /// Rotation around the origin of the object
+    pub rotate_self: Quat,
+    /// Uniform scale of the mesh, not applied to the children
+    pub scale: f32,
+    /// Whether the spin, tilt and translation keys move this object
+    pub controllable: bool,
+    /// Keyframes overriding the transform over time
+    pub animation: Animation,
+    /// Whether the object is drawn into the shadow map
+    pub cast_shadows: bool,
+    /// Whether the camera collides with the bounding box of the object
+    pub collides: bool,
+}
+
+impl SceneObject {
+    /// Moves the object to where its animation is at `time` seconds after the scene started
+    fn update(&mut self, time: f32) {
+        let animation = &self.animation;
+        let time = animation.local_time(time);
+
+        if let Some(translation) = animation.translation.sample(time) {
+            self.translation = translation;
+        }
+        if let Some(rotation) = animation.rotation.sample(time) {
+            self.rotation = rotation;
+        }
+        if let Some(rotate_self) = animation.rotate_self.sample(time) {
+            self.rotate_self = rotate_self;
+        }
+        if let Some(scale) = animation.scale.sample(time) {
+            self.scale = scale;
+        }
+    }
+
+    /// Returns the transform of the object, without any view information
+    pub fn transform(&self) -> Transform<'_> {
+        Transform {
+            translation: self.translation,
+            rotation: self.rotation,
+            rotate_self: self.rotate_self,
+            scale: self.scale,
+            texture: self.texture.as_ref(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Holds every object of the scene
+pub struct Scene {
+    /// Objects of the scene, parents listed before their children
+    pub objects: Vec<SceneObject>,
+    /// Color of the ambient light
+    pub ambient: [f32; 3],
+    /// Light sources of the scene, relative to the objects they are attached to
+    pub lights: Vec<Light>,
+    /// Index in `objects` of the object each of `lights` is attached to
+    pub light_objects: Vec<Option<usize>>,
+    /// Drawn behind every object, the clear color shows when there is none
+    pub skybox: Option<Skybox>,
+    /// Index in `objects` of the object the orbit camera turns around
+    pub focus: Option<usize>,
+    /// Box the camera is kept within, the box holding every object when `None`
+    pub bounds: Option<Aabb>,
+}
+
+impl Scene {
+    /// Reads a scene file and loads all the models and textures it references.
+    ///
+    /// Models and textures that fail to load are replaced by placeholders, and a skybox that fails
+    /// to load is left out, so that a single missing file doesn't prevent the rest of the scene from showing.
+    pub fn load<F: Facade>(display: &F, path: &str) -> Result<Scene, SceneError> {
+        let description = SceneDescription::read(path)?;
+
+        let mut objects = Vec::new();
+        for object in description.objects {
+            load_object(object, None, &mut objects, &mut |object| load_mesh(display, object))?;
+        }
+
+        let focus = description.focus.and_then(|focus| {
+            let index = objects.iter().position(|object| object.name == focus);
+            if index.is_none() {
+                eprintln!("Could not find focused object {focus}");
+            }
+            index
+        });
+
+        let light_objects = description.lights.iter()
+            .map(|light| {
+                let name = light.attach.as_ref()?;
+                let index = objects.iter().position(|object| &object.name == name);
+                if index.is_none() {
+                    eprintln!("Could not find object {name} the light is attached to");
+                }
+                index
+            })
+            .collect();
+
+        let skybox = description.skybox.and_then(|skybox| {
+            skybox.load(display)
+                .map_err(|e| eprintln!("Could not load skybox: {e}"))
+                .ok()
+        });
+
+        Ok(Scene {
+            objects,
+            ambient: description.ambient,
+            lights: description.lights,
+            light_objects,
+            skybox,
+            focus,
+            bounds: description.bounds,
+        })
+    }
+
+    /// Moves every object to where its animation is at `time` seconds after the scene started
+    pub fn update(&mut self, time: f32) {
+        self.objects.iter_mut().for_each(|object| object.update(time));
+    }
+
+    /// Position of the origin of an object in the world, at the current time
+    pub fn world_position(&self, index: usize) -> [f32; 3] {
+        let frame = self.transforms(SceneObject::transform)[index].get_frame();
+        [frame[3][0], frame[3][1], frame[3][2]]
+    }
+
+    /// Lights of the scene placed in the world, following the objects they are attached to as they are placed by
+    /// `local` like in `transforms`
+    pub fn world_lights<'a>(&'a self, local: impl Fn(&'a SceneObject) -> Transform<'a>) -> Vec<Light> {
+        let transforms = self.transforms(local);
+        self.lights.iter()
+            .zip(&self.light_objects)
+            .map(|(light, object)| match object {
+                Some(index) => light.placed(transforms[*index].get_frame()),
+                None => light.clone(),
+            })
+            .collect()
+    }
+
+    /// Collects the world space bounding boxes of the objects, as they are placed by `local` like in `transforms`
+    pub fn collision_world<'a>(&'a self, local: impl Fn(&'a SceneObject) -> Transform<'a>) -> CollisionWorld {
+        let boxes: Vec<(&SceneObject, Aabb)> = self.objects.iter()
+            .zip(self.transforms(local))
+            .filter_map(|(object, transform)| {
+                let model = object.model.as_ref()?;
+                Some((object, model.model_data.bounds.transformed(&transform.get_model())))
+            })
+            .collect();
+
+        let bounds = self.bounds.unwrap_or_else(|| {
+            boxes.iter()
+                .map(|(_, bounds)| *bounds)
+                .reduce(|a, b| a.union(&b))
+                .unwrap_or_default()
+        });
+        let colliders = boxes.into_iter()
+            .filter(|(object, _)| object.collides)
+            .map(|(_, bounds)| bounds)
+            .collect();
+
+        CollisionWorld { bounds, colliders }
+    }
+
+    /// Computes the transform of every object, in the order of `objects`.
+    ///
+    /// `local` gives the transform of an object relative to its parent, the matrix of the parent
+    /// is then filled in by walking down the hierarchy.
+    pub fn transforms<'a>(&'a self, local: impl Fn(&'a SceneObject) -> Transform<'a>) -> Vec<Transform<'a>> {
+        let mut transforms: Vec<Transform> = Vec::with_capacity(self.objects.len());
+        for object in &self.objects {
+            let mut transform = local(object);
+            // Parents are loaded before their children, so their transform is already known
+            if let Some(parent) = object.parent {
+                transform.parent = transforms[parent].get_frame();
+            }
+            transforms.push(transform);
+        }
+        transforms
+    }
+}
+
+/// Loads the model and texture of an object, `None` for empty objects
+fn load_mesh<F: Facade>(display: &F, object: &ObjectDescription) -> Result<(Option<GenericModel>, Option<SrgbTexture2d>), SceneError> {
+    let model = match &object.mesh {
+        MeshDescription::Obj(src) => GenericModel::from_obj(display, src.clone(), &object.import)
+            .or_else(|e| {
+                eprintln!("Could not load model {src}: {e}");
+                GenericModel::placeholder(display, PLACEHOLDER_SIZE / object.scale)
+            })?,
+        MeshDescription::Quad { corners, tex_coords, normal } => {
+            let vertices: Vec<Vertex> = corners.iter()
+                .zip(tex_coords)
+                .map(|(position, tex_coords)| Vertex { position: *position, tex_coords: *tex_coords })
+                .collect();
+            let normals = vec![Normal { normal: *normal }; 4];
+            GenericModel::new(display, &vertices, &[0, 1, 2, 2, 3, 0], &normals)?
+        }
+        MeshDescription::Empty => return Ok((None, None)),
+    };
+
+    let texture = load_texture(display, &object.texture)
+        .or_else(|e| {
+            eprintln!("Could not load texture {}: {e}", object.texture);
+            placeholder_texture(display)
+        })?;
+
+    Ok((Some(model), Some(texture)))
+}
+
+/// Loads an object and its children, appending them to `objects` with each parent before its children.
+///
+/// `load_mesh` gives the model and texture of each object.
+fn load_object<M>(mut object: ObjectDescription, parent: Option<usize>, objects: &mut Vec<SceneObject>, load_mesh: &mut M) -> Result<(), SceneError>
+where
+    M: FnMut(&ObjectDescription) -> Result<(Option<GenericModel>, Option<SrgbTexture2d>), SceneError>,
+{
+    let (model, texture) = load_mesh(&object)?;
+
+    object.animation.sort();
+
+    let index = objects.len();
+    objects.push(SceneObject {
+        name: object.name,
+        texture,
+        model,
+        parent,
+        translation: object.translation,
+        rotation: euler_rotation(object.rotation),
+        rotate_self: euler_rotation(object.rotate_self),
+        scale: object.scale,
+        controllable: object.controllable,
+        animation: object.animation,
+        cast_shadows: object.cast_shadows,
+        collides: object.collides,
+    });
+
+    for child in object.children {
+        load_object(child, Some(index), objects, load_mesh)?;
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::assets::matrices::multiply_matrices;
+
+    /// Path of a file of the temporary directory, unique to this process
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ogl_house_scene_{}_{name}", std::process::id()))
+    }
+
+    /// Writes `contents` to a file of the temporary directory and returns its path
+    fn temp_file(name: &str, contents: &str) -> PathBuf {
+        let path = temp_path(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// Scene of the objects described in RON, all of them empty so that no OpenGL context is needed
+    fn scene(objects: &str) -> Scene {
+        let descriptions: Vec<ObjectDescription> = ron::from_str(objects).unwrap();
+        let mut objects = Vec::new();
+        for object in descriptions {
+            load_object(object, None, &mut objects, &mut |_| Ok((None, None))).unwrap();
+        }
+        Scene { objects, ambient: [1.0; 3], lights: Vec::new(), light_objects: Vec::new(), skybox: None, focus: None, bounds: None }
+    }
+
+    const HIERARCHY: &str = r#"[
+        (name: "root", mesh: Empty, translation: (1.0, 0.0, 0.0), scale: 2.0, children: [
+            (name: "child", mesh: Empty, translation: (0.0, 0.0, 3.0), scale: 0.5, children: [
+                (name: "grandchild", mesh: Empty, translation: (0.0, 1.0, 0.0)),
+            ]),
+            (name: "sibling", mesh: Empty),
+        ]),
+        (name: "spinner", mesh: Empty, translation: (0.0, 2.0, 0.0), rotation: (0.0, 1.0, 0.0), rotate_self: (0.5, 0.0, 0.0), scale: 3.0, children: [
+            (name: "moon", mesh: Empty, translation: (1.0, 0.0, 0.0), rotation: (0.3, 0.0, 0.0), scale: 0.2),
+        ]),
+    ]"#;
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        assert!((0..3).all(|i| (a[i] - b[i]).abs() < 1e-5), "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn children_after_their_parent() {
+        let scene = scene(HIERARCHY);
+
+        let names: Vec<&str> = scene.objects.iter().map(|object| object.name.as_str()).collect();
+        assert_eq!(names, ["root", "child", "grandchild", "sibling", "spinner", "moon"]);
+        let parents: Vec<Option<usize>> = scene.objects.iter().map(|object| object.parent).collect();
+        assert_eq!(parents, [None, Some(0), Some(1), Some(0), None, Some(4)]);
+    }
+
+    #[test]
+    fn children_follow_the_frame_of_their_parent() {
+        let scene = scene(HIERARCHY);
+        let transforms = scene.transforms(SceneObject::transform);
+
+        for (index, object) in scene.objects.iter().enumerate() {
+            let local = object.transform().get_model();
+            let expected = match object.parent {
+                Some(parent) => multiply_matrices(&transforms[parent].get_frame(), &local),
+                None => local,
+            };
+            let model = transforms[index].get_model();
+            for column in 0..4 {
+                for row in 0..4 {
+                    assert!((model[column][row] - expected[column][row]).abs() < 1e-5, "{}: {model:?} != {expected:?}", object.name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn scale_is_not_inherited() {
+        let scene = scene(HIERARCHY);
+
+        // Scaling the root by 2 and the child by 0.5 moves neither of their children
+        assert_close(scene.world_position(1), [1.0, 0.0, 3.0]);
+        assert_close(scene.world_position(2), [1.0, 1.0, 3.0]);
+        assert_close(scene.world_position(3), [1.0, 0.0, 0.0]);
+    }
+
+    fn names(description: &SceneDescription) -> Vec<&str> {
+        description.objects.iter().map(|object| object.name.as_str()).collect()
+    }
+
+    #[test]
+    fn base_scene() {
+        let base = temp_file("base_scene.ron", r#"(
+            ambient: (0.5, 0.5, 0.5),
+            lights: [(kind: Directional(direction: (0.0, -1.0, 0.0))), (kind: Directional(direction: (1.0, 0.0, 0.0)))],
+            skybox: Some(Cross("textures/dawn.jpg")),
+            focus: Some("a"),
+            bounds: Some((min: (-1.0, 0.0, -1.0), max: (1.0, 1.0, 1.0))),
+            objects: [(name: "a", mesh: Empty), (name: "b", mesh: Empty, children: [(name: "c", mesh: Empty)])],
+        )"#);
+        let scene = temp_file("derived_scene.ron", &format!(r#"(
+            base: Some({:?}),
+            ambient: (0.1, 0.1, 0.1),
+            focus: Some("d"),
+            objects: [(name: "d", mesh: Empty)],
+        )"#, base.to_str().unwrap()));
+
+        let description = SceneDescription::read(scene.to_str().unwrap());
+        fs::remove_file(&base).ok();
+        fs::remove_file(&scene).ok();
+        let description = description.unwrap();
+
+        assert_eq!(names(&description), ["a", "b", "d"]);
+        assert_eq!(description.objects[1].children[0].name, "c");
+        // Taken from the base when the scene has none
+        assert!(description.skybox.is_some());
+        assert_eq!(description.bounds, Some(Aabb { min: [-1.0, 0.0, -1.0], max: [1.0; 3] }));
+        // Never taken from the base
+        assert_eq!(description.focus.as_deref(), Some("d"));
+        assert_eq!(description.ambient, [0.1; 3]);
+        assert_eq!(description.lights.len(), default_lights().len());
+    }
+
+    #[test]
+    fn night_scene_shares_the_objects_of_the_day() {
+        let day = SceneDescription::read(DEFAULT_SCENE).unwrap();
+        let night = SceneDescription::read("scenes/gas_station_night.ron").unwrap();
+
+        assert_eq!(names(&night), names(&day));
+        assert_eq!(night.focus, day.focus);
+        assert_eq!(night.bounds, day.bounds);
+        assert_ne!(night.ambient, day.ambient);
+    }
+
+    #[test]
+    fn base_cycles_are_errors() {
+        let (a, b) = (temp_path("cycle_a.ron"), temp_path("cycle_b.ron"));
+        let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+        fs::write(a, format!("(base: Some({b:?}))")).unwrap();
+        fs::write(b, format!("(base: Some({a:?}))")).unwrap();
+
+        let result = SceneDescription::read(a);
+        fs::remove_file(a).ok();
+        fs::remove_file(b).ok();
+        assert!(matches!(result, Err(SceneError::BaseCycle(path)) if path == a));
+    }
+}
@@ -0,0 +1,205 @@
+//! Golden image tests: fixed scenes rendered offscreen from fixed cameras and compared against the reference
+//! images in `tests/golden`.
+//!
+//! Run with `UPDATE_GOLDEN=1` to write the references again after an intended change of the output. When an
+//! image differs from its reference, the image and a diff highlighting the differences in red are written to
+//! `target/golden`.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use image::{Rgba, RgbaImage};
+
+use ogl_house_scene::camera::Camera;
+use ogl_house_scene::headless::{headless_context, render_scene, HeadlessError};
+
+/// Size, in pixels, of the rendered images, small to keep the references light
+const SIZE: (u32, u32) = (320, 240);
+
+/// Largest perceptual difference, as computed by `perceptual_delta`
+const MAX_DELTA: f32 = 35215.0;
+
+/// How different an image may be from its reference
+#[derive(Copy, Clone, Debug)]
+struct Tolerance {
+    /// Largest difference of each color channel ignored outright, absorbing rounding
+    channel: u8,
+    /// Largest perceptual difference of a pixel, between 0 and 1, that doesn't count it as mismatched
+    perceptual: f32,
+    /// Fraction of the pixels that may be mismatched
+    mismatched: f32,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Tolerance { channel: 2, perceptual: 0.1, mismatched: 0.001 }
+    }
+}
+
+/// How an image differs from its reference
+struct Comparison {
+    /// Number of pixels over the perceptual tolerance
+    mismatched: usize,
+    /// Largest perceptual difference of a pixel, between 0 and 1
+    max_delta: f32,
+    /// The reference faded to grey, with the mismatched pixels in red
+    diff: RgbaImage,
+}
+
+/// Luma and chrominance of a color, in which distances are closer to the differences the eye sees
+fn yiq(Rgba([r, g, b, _]): Rgba<u8>) -> [f32; 3] {
+    let [r, g, b] = [r, g, b].map(f32::from);
+    [
+        r * 0.298_895_3 + g * 0.586_622_5 + b * 0.114_482_23,
+        r * 0.595_977_99 - g * 0.274_176_1 - b * 0.321_801_9,
+        r * 0.211_470_17 - g * 0.522_617_1 + b * 0.311_146_94,
+    ]
+}
+
+/// Perceived difference between two colors, between 0 and 1, weighting luma over chrominance like pixelmatch
+fn perceptual_delta(a: Rgba<u8>, b: Rgba<u8>) -> f32 {
+    let ([ya, ia, qa], [yb, ib, qb]) = (yiq(a), yiq(b));
+    let delta = 0.5053 * (ya - yb).powi(2) + 0.299 * (ia - ib).powi(2) + 0.1957 * (qa - qb).powi(2);
+    (delta / MAX_DELTA).sqrt()
+}
+
+/// Compares an image against its reference, both of the same size
+fn compare(image: &RgbaImage, reference: &RgbaImage, tolerance: Tolerance) -> Comparison {
+    let mut diff = RgbaImage::new(reference.width(), reference.height());
+    let mut mismatched = 0;
+    let mut max_delta = 0.0f32;
+
+    for ((actual, expected), out) in image.pixels().zip(reference.pixels()).zip(diff.pixels_mut()) {
+        let close = actual.0.iter().zip(expected.0).all(|(a, e)| a.abs_diff(e) <= tolerance.channel);
+        let delta = if close { 0.0 } else { perceptual_delta(*actual, *expected) };
+        max_delta = max_delta.max(delta);
+
+        *out = if delta > tolerance.perceptual {
+            mismatched += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let faded = (255.0 - (255.0 - yiq(*expected)[0]) * 0.1) as u8;
+            Rgba([faded, faded, faded, 255])
+        };
+    }
+
+    Comparison { mismatched, max_delta, diff }
+}
+
+/// One of the images checked
+struct Case {
+    name: &'static str,
+    scene: &'static str,
+    /// Position, yaw and pitch of the camera
+    camera: ([f32; 3], f32, f32),
+    /// Seconds after the scene started
+    time: f32,
+}
+
+const CASES: [Case; 3] = [
+    Case { name: "gas_station_front", scene: "scenes/gas_station.ron", camera: ([0.5, 0.4, 1.5], 3.4, -0.15), time: 3.0 },
+    Case { name: "gas_station_start", scene: "scenes/gas_station.ron", camera: ([0.0, 0.1, 0.0], 0.0, 0.0), time: 0.0 },
+    Case { name: "gas_station_night", scene: "scenes/gas_station_night.ron", camera: ([0.5, 0.4, 1.5], 3.4, -0.15), time: 1.5 },
+];
+
+fn golden_dir(dir: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(dir)
+}
+
+/// Checks an image against its reference, writing it and its diff under `target/golden` when they differ
+fn check(name: &str, image: &RgbaImage, tolerance: Tolerance) -> Result<(), String> {
+    let reference_path = golden_dir("tests/golden").join(format!("{name}.png"));
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        image.save(&reference_path).map_err(|e| format!("could not write {}: {e}", reference_path.display()))?;
+        println!("Updated {}", reference_path.display());
+        return Ok(());
+    }
+
+    let reference = image::open(&reference_path)
+        .map_err(|e| format!("could not read {}, run with UPDATE_GOLDEN=1 to create it: {e}", reference_path.display()))?
+        .to_rgba8();
+
+    let output_dir = golden_dir("target/golden");
+    fs::create_dir_all(&output_dir).map_err(|e| format!("could not create {}: {e}", output_dir.display()))?;
+    let actual_path = output_dir.join(format!("{name}.png"));
+
+    if image.dimensions() != reference.dimensions() {
+        image.save(&actual_path).ok();
+        return Err(format!("{name}: rendered {:?} but the reference is {:?}", image.dimensions(), reference.dimensions()));
+    }
+
+    let comparison = compare(image, &reference, tolerance);
+    let allowed = (tolerance.mismatched * (image.width() * image.height()) as f32) as usize;
+    if comparison.mismatched <= allowed {
+        return Ok(());
+    }
+
+    let diff_path = output_dir.join(format!("{name}.diff.png"));
+    image.save(&actual_path).ok();
+    comparison.diff.save(&diff_path).ok();
+    Err(format!(
+        "{name}: {} pixels differ from the reference, {allowed} allowed, by up to {:.3}, see {} and {}",
+        comparison.mismatched,
+        comparison.max_delta,
+        actual_path.display(),
+        diff_path.display(),
+    ))
+}
+
+/// All cases share a single context and run in turn, EGL displays being shared between the threads of the tests
+#[test]
+fn golden_images() {
+    let context = headless_context().expect("could not create an offscreen context");
+
+    let failures: Vec<String> = CASES.iter().filter_map(|case| {
+        let (position, yaw, pitch) = case.camera;
+        let camera = Camera { position, yaw, pitch, ..Camera::default() };
+        let image = render_scene(&context, case.scene, &camera, SIZE, case.time)
+            .unwrap_or_else(|e| panic!("could not render {}: {e}", case.name));
+        check(case.name, &image, Tolerance::default()).err()
+    }).collect();
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+
+    // Empty images are refused rather than rendered
+    for size in [(0, 240), (320, 0)] {
+        let result = render_scene(&context, CASES[0].scene, &Camera::default(), size, 0.0);
+        assert!(matches!(result, Err(HeadlessError::Size(..))), "{size:?} was not refused");
+    }
+}
+
+#[test]
+fn identical_images_match() {
+    let image = RgbaImage::from_fn(8, 8, |x, y| Rgba([(x * 32) as u8, (y * 32) as u8, 128, 255]));
+    let comparison = compare(&image, &image, Tolerance::default());
+    assert_eq!(comparison.mismatched, 0);
+    assert_eq!(comparison.max_delta, 0.0);
+}
+
+#[test]
+fn small_differences_are_tolerated() {
+    let reference = RgbaImage::from_pixel(8, 8, Rgba([100, 150, 200, 255]));
+    let mut image = reference.clone();
+    // Within the channel tolerance
+    image.put_pixel(0, 0, Rgba([102, 149, 200, 255]));
+    // Beyond it, but hardly visible
+    image.put_pixel(1, 0, Rgba([106, 150, 200, 255]));
+
+    let comparison = compare(&image, &reference, Tolerance::default());
+    assert_eq!(comparison.mismatched, 0);
+    assert!(comparison.max_delta > 0.0);
+}
+
+#[test]
+fn visible_differences_are_marked() {
+    let reference = RgbaImage::from_pixel(8, 8, Rgba([100, 150, 200, 255]));
+    let mut image = reference.clone();
+    image.put_pixel(3, 5, Rgba([255, 255, 255, 255]));
+
+    let comparison = compare(&image, &reference, Tolerance::default());
+    assert_eq!(comparison.mismatched, 1);
+    assert_eq!(*comparison.diff.get_pixel(3, 5), Rgba([255, 0, 0, 255]));
+    assert_ne!(*comparison.diff.get_pixel(0, 0), Rgba([255, 0, 0, 255]));
+}